@@ -0,0 +1,22 @@
+# Checks that `jano` compiles with every supported combination of features.
+# Needs the android targets and the NDK (see read_me_before_running.sh), as android-activity compiles C code.
+# Usage: sh check_features.sh [target]
+
+set -e
+TARGET="${1:-aarch64-linux-android}"
+
+for FEATURES in \
+    "" \
+    "serde" \
+    "wgpu-0.19" \
+    "wgpu-0.20" \
+    "wgpu-0.19,canvas" \
+    "wgpu-0.20,canvas" \
+    "egui-0.27" \
+    "egui-0.27,accesskit" \
+    "egui-0.28" \
+    "egui-0.28,accesskit"
+do
+    echo "-- Checking jano with features \"$FEATURES\""
+    cargo check -p jano --target "$TARGET" --features "$FEATURES"
+done
@@ -1,16 +1,18 @@
 use jano::android_activity::{AndroidApp, MainEvent};
-use jano::glam::uvec2;
-use jano::graphics::Gpu;
-use jano::{wgpu, FrameStats, Window};
+use jano::glam::vec2;
+use jano::graphics::canvas::{Canvas, Color, Rect};
+use jano::graphics::{Gpu, GpuConfig};
+use jano::{FrameStats, Window};
 
 #[no_mangle]
 fn android_main(android: AndroidApp) {
-    jano::android_main(android, App::default());
+    jano::android_main(android, App::default(), 60);
 }
 
 #[derive(Default)]
 struct App {
     gpu: Option<Gpu>,
+    canvas: Option<Canvas>,
     window: Option<Window>,
     hue: f64,
 }
@@ -22,27 +24,41 @@ impl jano::AppState for App {
             MainEvent::InitWindow { .. } => {
                 self.window = jano::android().native_window();
 
-                if let Some(win) = &self.window {
-                    let instance = wgpu::Instance::new(Default::default());
-                    let surface = jano::graphics::create_wgpu_surface(&instance, win);
-
-                    let size = uvec2(win.width() as u32, win.height() as u32);
-                    self.gpu = Some(pollster::block_on(Gpu::new(instance, surface, size)));
+                if let (Some(win), Some(gpu)) = (&self.window, &mut self.gpu) {
+                    if let Err(err) = gpu.resume(win) {
+                        eprintln!("Failed to resume GPU: {err}");
+                    }
+                } else if let Some(win) = &self.window {
+                    match pollster::block_on(Gpu::new(&GpuConfig::default(), win)) {
+                        Ok(gpu) => {
+                            self.canvas = Some(Canvas::new(&gpu));
+                            self.gpu = Some(gpu);
+                        }
+                        Err(err) => eprintln!("Failed to create GPU: {err}"),
+                    }
                 } else {
                     eprintln!("native_window() returned None during InitWindow callback");
                 }
             }
             MainEvent::TerminateWindow { .. } => {
-                self.gpu = None;
+                // The surface has to be dropped before the window is released
+                if let Some(gpu) = &mut self.gpu {
+                    gpu.suspend();
+                }
                 self.window = None;
             }
+            MainEvent::ConfigChanged { .. } => {
+                if let Some(canvas) = &mut self.canvas {
+                    canvas.refresh_density();
+                }
+            }
             MainEvent::Destroy => return true,
             _ => {}
         }
         false
     }
-    fn on_frame(&mut self, _stats: FrameStats) {
-        let Some(gpu) = &self.gpu else {
+    fn on_frame(&mut self, stats: FrameStats) {
+        let (Some(gpu), Some(canvas)) = (&mut self.gpu, &mut self.canvas) else {
             return;
         };
 
@@ -53,30 +69,36 @@ impl jano::AppState for App {
                 return;
             }
         };
-        let mut encoder = gpu.create_command_encoder();
-        // --- render pass ---
+        if let Some(size) = gpu.take_resized() {
+            eprintln!("Surface resized to {size}");
+        }
 
-        let (r, g, b) = hsv::hsv_to_rgb(self.hue, 1.0, 1.0);
-        let (r, g, b, a) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0, 1.0);
-        let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            occlusion_query_set: None,
-            timestamp_writes: None,
-            label: Some("#render_pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-        });
-        std::mem::drop(pass);
+        // --- draw ---
+        canvas.begin_frame(gpu.surface_size());
+        let area = canvas.safe_rect().shrink(16.0);
+        let panel = Rect::from_min_size(area.min, vec2(area.size().x, 120.0));
+        canvas.fill_rounded_rect(panel, 12.0, Color::BLACK.with_alpha(160));
+        canvas.stroke_rect(panel, 12.0, 2.0, Color::WHITE);
+        canvas.text(
+            panel.min + 16.0,
+            32.0,
+            Color::WHITE,
+            &format!("Jano Example\n{} fps", stats.fps),
+        );
+
+        let center = area.center();
+        let angle = (self.hue as f32).to_radians();
+        let hand = vec2(angle.cos(), angle.sin()) * 100.0;
+        canvas.fill_circle(center, 120.0, Color::rgb(30, 30, 30));
+        canvas.stroke_circle(center, 120.0, 4.0, Color::WHITE);
+        canvas.line(center, center + hand, 8.0, Color::WHITE);
 
         // --- submit passes ---
+        let (r, g, b) = hsv::hsv_to_rgb(self.hue, 1.0, 1.0);
+        let mut encoder = gpu.create_command_encoder();
+        canvas.render(gpu, &mut encoder, &view, Some(Color::rgb(r, g, b)));
         gpu.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        gpu.present(output);
 
         self.hue += 2.0;
         if self.hue >= 360.0 {
@@ -44,9 +44,11 @@ pub fn create_android_project(
     _ = fs::create_dir_all(&java_src);
     _ = fs::create_dir_all(format!("{main}/res/mipmap-mdpi"));
     _ = fs::create_dir_all(format!("{main}/res/values"));
+    _ = fs::create_dir_all(format!("{main}/res/xml"));
 
     mv!("ic_launcher.png", &format!("{main}/res/mipmap-mdpi"))?;
     mv!("themes.xml", &format!("{main}/res/values"))?;
+    mv!("file_paths.xml", &format!("{main}/res/xml"))?;
     mv!("build.gradle", &path)?;
     mv!("settings.gradle", &path)?;
     mv!("gradle.properties", &path)?;
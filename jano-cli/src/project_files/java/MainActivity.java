@@ -14,6 +14,17 @@ import com.google.androidgamesdk.GameActivity;
 import android.os.Build.VERSION;
 import android.os.Build.VERSION_CODES;
 import android.os.Bundle;
+import android.content.ActivityNotFoundException;
+import android.graphics.Matrix;
+import android.net.Uri;
+import android.view.PointerIcon;
+import android.view.inputmethod.CursorAnchorInfo;
+import android.graphics.Rect;
+import android.view.accessibility.AccessibilityEvent;
+import android.view.accessibility.AccessibilityManager;
+import android.view.accessibility.AccessibilityNodeInfo;
+import android.view.accessibility.AccessibilityNodeProvider;
+import android.view.InputDevice;
 import android.view.View;
 import android.view.WindowManager;
 import android.view.inputmethod.InputMethodManager;
@@ -22,9 +33,16 @@ import android.content.ClipboardManager;
 import android.content.ClipDescription;
 import android.content.ClipData;
 import android.content.Intent;
+import android.hardware.input.InputManager;
 import android.widget.Toast;
 
 import android.graphics.Bitmap;
+import android.graphics.BitmapFactory;
+import androidx.core.content.FileProvider;
+import java.io.File;
+import java.io.FileOutputStream;
+import java.io.IOException;
+import java.io.InputStream;
 import java.nio.ByteBuffer;
 
 import java.util.Date;
@@ -55,8 +73,22 @@ public class MainActivity extends GameActivity {
 		return offsetFromUtc;
     }
     
+    // Returns the scale factor from dp to pixels of the display.
+    public float getDisplayDensity() {
+        return getResources().getDisplayMetrics().density;
+    }
+
+    // Returns the user's font size preference, as a factor of the default size.
+    public float getFontScale() {
+        return getResources().getConfiguration().fontScale;
+    }
+
     native public static void onDisplayInsets(int[] cutouts);
 	native public static void onPictureTaken(byte[] data, int w, int h);
+    native public static void onGamepadConnected(int deviceId, String name);
+    native public static void onInputDeviceRemoved(int deviceId);
+    native public static void onAccessibilityAction(int virtualViewId, int action, String text);
+    native public static void onClipboardChanged();
    	
     // ArrayList<EditText> visibleTextFields = new ArrayList();
     
@@ -153,22 +185,161 @@ public class MainActivity extends GameActivity {
     	return SocketWrapper.connect(addressStr, port, timeout);
     }
     
-    public String getClipboardContent() {
+    private ClipData.Item primaryClipItem() {
         ClipboardManager clipboard = (ClipboardManager) getSystemService(Context.CLIPBOARD_SERVICE);
-        if (clipboard != null && clipboard.hasPrimaryClip() && clipboard.getPrimaryClip() != null) {
-            CharSequence clip = clipboard.getPrimaryClip().getItemAt(0).coerceToText(MainActivity.this).toString();
-            return clip.toString();
+        if (clipboard == null || !clipboard.hasPrimaryClip()) {
+            return null;
+        }
+        ClipData clip = clipboard.getPrimaryClip();
+        if (clip == null || clip.getItemCount() == 0) {
+            return null;
         }
-        return new String();
+        return clip.getItemAt(0);
     }
-    public void setClipboardContent(String text) {
-    	if(text == null) {
-    		System.err.println("MainActivity.setClipboardContent called with a null String");
-    		return;
-    	}
-    	
-    	ClipboardManager clipboard = (ClipboardManager) getSystemService(Context.CLIPBOARD_SERVICE);
-    	clipboard.setPrimaryClip(new ClipData(new ClipDescription("text", new String[] { "text/html" }), new ClipData.Item(text)));
+
+    public String[] getClipboardMimeTypes() {
+        ClipboardManager clipboard = (ClipboardManager) getSystemService(Context.CLIPBOARD_SERVICE);
+        if (clipboard == null || !clipboard.hasPrimaryClip()) {
+            return new String[0];
+        }
+        ClipDescription description = clipboard.getPrimaryClipDescription();
+        if (description == null) {
+            return new String[0];
+        }
+        String[] mimeTypes = new String[description.getMimeTypeCount()];
+        for (int i = 0; i < mimeTypes.length; i++) {
+            mimeTypes[i] = description.getMimeType(i);
+        }
+        return mimeTypes;
+    }
+
+    public String getClipboardText() {
+        ClipData.Item item = primaryClipItem();
+        if (item == null) {
+            return null;
+        }
+        return item.coerceToText(this).toString();
+    }
+
+    public String getClipboardHtml() {
+        ClipData.Item item = primaryClipItem();
+        return item == null ? null : item.getHtmlText();
+    }
+
+    public String getClipboardUri() {
+        ClipData.Item item = primaryClipItem();
+        if (item == null || item.getUri() == null) {
+            return null;
+        }
+        return item.getUri().toString();
+    }
+
+    // Returns the pixels (RGBA, 1 byte per channel) of the image the clip's URI points to, or null.
+    // The width and height are written into sizeOut.
+    public byte[] getClipboardImage(int[] sizeOut) {
+        ClipData.Item item = primaryClipItem();
+        if (item == null || item.getUri() == null) {
+            return null;
+        }
+        Bitmap bitmap;
+        try (InputStream stream = getContentResolver().openInputStream(item.getUri())) {
+            bitmap = BitmapFactory.decodeStream(stream);
+        } catch (IOException | SecurityException e) {
+            System.err.println("Failed to read clipboard image: " + e);
+            return null;
+        }
+        if (bitmap == null) {
+            return null;
+        }
+        bitmap = bitmap.copy(Bitmap.Config.ARGB_8888, false);
+        byte[] rawBuf = new byte[bitmap.getByteCount()];
+        bitmap.copyPixelsToBuffer(ByteBuffer.wrap(rawBuf));
+        sizeOut[0] = bitmap.getWidth();
+        sizeOut[1] = bitmap.getHeight();
+        return rawBuf;
+    }
+
+    private void setPrimaryClip(ClipData clip) {
+        ClipboardManager clipboard = (ClipboardManager) getSystemService(Context.CLIPBOARD_SERVICE);
+        clipboard.setPrimaryClip(clip);
+    }
+
+    public void setClipboardText(String text) {
+        setPrimaryClip(ClipData.newPlainText("text", text));
+    }
+
+    public void setClipboardHtml(String html, String text) {
+        setPrimaryClip(ClipData.newHtmlText("html", text, html));
+    }
+
+    public void setClipboardUri(String uri) {
+        setPrimaryClip(ClipData.newRawUri("uri", Uri.parse(uri)));
+    }
+
+    // Saves the image (RGBA, 1 byte per channel) as a PNG shared through the FileProvider.
+    // Returns false if the image couldn't be saved.
+    public boolean setClipboardImage(byte[] data, int w, int h) {
+        Bitmap bitmap = Bitmap.createBitmap(w, h, Bitmap.Config.ARGB_8888);
+        bitmap.copyPixelsFromBuffer(ByteBuffer.wrap(data));
+        File dir = new File(getCacheDir(), "clipboard");
+        dir.mkdirs();
+        File file = new File(dir, "image.png");
+        try (FileOutputStream out = new FileOutputStream(file)) {
+            bitmap.compress(Bitmap.CompressFormat.PNG, 100, out);
+        } catch (IOException e) {
+            System.err.println("Failed to save clipboard image: " + e);
+            return false;
+        }
+        Uri uri = FileProvider.getUriForFile(this, getPackageName() + ".fileprovider", file);
+        setPrimaryClip(ClipData.newUri(getContentResolver(), "image", uri));
+        return true;
+    }
+
+    private void createClipboardListener() {
+        ClipboardManager clipboard = (ClipboardManager) getSystemService(Context.CLIPBOARD_SERVICE);
+        clipboard.addPrimaryClipChangedListener(() -> onClipboardChanged());
+    }
+
+    // Returns false if no app can open the URL.
+    public boolean openUrl(String url) {
+        Intent intent = new Intent(Intent.ACTION_VIEW, Uri.parse(url));
+        intent.addFlags(Intent.FLAG_ACTIVITY_NEW_TASK);
+        try {
+            startActivity(intent);
+            return true;
+        } catch (ActivityNotFoundException e) {
+            return false;
+        }
+    }
+
+    public void setImeCursorRect(int left, int top, int right, int bottom) {
+        runOnUiThread(() -> {
+            View view = mSurfaceView;
+            if (view == null) {
+                return;
+            }
+            int[] location = new int[2];
+            view.getLocationOnScreen(location);
+            Matrix matrix = new Matrix();
+            matrix.setTranslate(location[0], location[1]);
+            CursorAnchorInfo info = new CursorAnchorInfo.Builder()
+                    .setMatrix(matrix)
+                    .setInsertionMarkerLocation(left, top, bottom, bottom, CursorAnchorInfo.FLAG_HAS_VISIBLE_REGION)
+                    .build();
+            InputMethodManager imm = (InputMethodManager) getSystemService(Context.INPUT_METHOD_SERVICE);
+            imm.updateCursorAnchorInfo(view, info);
+        });
+    }
+
+    public void setPointerIcon(int type) {
+        if (VERSION.SDK_INT < VERSION_CODES.N) {
+            return;
+        }
+        runOnUiThread(() -> {
+            if (mSurfaceView != null) {
+                mSurfaceView.setPointerIcon(PointerIcon.getSystemIcon(this, type));
+            }
+        });
     }
 
     public void showSoftKeyboard() {
@@ -230,6 +401,44 @@ public class MainActivity extends GameActivity {
         });
     }
 
+    private static void reportIfGamepad(int deviceId) {
+        InputDevice device = InputDevice.getDevice(deviceId);
+        if (device == null) {
+            return;
+        }
+        int sources = device.getSources();
+        if ((sources & InputDevice.SOURCE_GAMEPAD) == InputDevice.SOURCE_GAMEPAD
+                || (sources & InputDevice.SOURCE_JOYSTICK) == InputDevice.SOURCE_JOYSTICK) {
+            onGamepadConnected(deviceId, device.getName());
+        }
+    }
+
+    private void createGamepadListener() {
+        // Listener for gamepads being connected and disconnected to pass into native code.
+        InputManager inputManager = (InputManager) getSystemService(Context.INPUT_SERVICE);
+        inputManager.registerInputDeviceListener(new InputManager.InputDeviceListener() {
+            @Override
+            public void onInputDeviceAdded(int deviceId) {
+                reportIfGamepad(deviceId);
+            }
+
+            @Override
+            public void onInputDeviceRemoved(int deviceId) {
+                MainActivity.onInputDeviceRemoved(deviceId);
+            }
+
+            @Override
+            public void onInputDeviceChanged(int deviceId) {}
+        }, null);
+    }
+
+    // Called by native code when it starts tracking gamepads, to report the ones already connected.
+    public void reportGamepads() {
+        for (int deviceId : InputDevice.getDeviceIds()) {
+            reportIfGamepad(deviceId);
+        }
+    }
+
     public void hideSystemUI() {
         // This will put the game behind any cutouts and waterfalls on devices which have
         // them, so the corresponding insets will be non-zero.
@@ -248,13 +457,203 @@ public class MainActivity extends GameActivity {
                 WindowInsetsControllerCompat.BEHAVIOR_SHOW_TRANSIENT_BARS_BY_SWIPE);
     }
 
+    // Flags of the accessibility nodes, must match the `A11Y_*` constants in jano/src/accessibility.rs.
+    private static final int A11Y_ENABLED = 1 << 0;
+    private static final int A11Y_FOCUSABLE = 1 << 1;
+    private static final int A11Y_FOCUSED = 1 << 2;
+    private static final int A11Y_CLICKABLE = 1 << 3;
+    private static final int A11Y_CHECKABLE = 1 << 4;
+    private static final int A11Y_CHECKED = 1 << 5;
+    private static final int A11Y_EDITABLE = 1 << 6;
+    private static final int A11Y_ADJUSTABLE = 1 << 7;
+    private static final int A11Y_PASSWORD = 1 << 8;
+
+    // The egui widgets exposed to accessibility services, set from native code.
+    private final Object a11yLock = new Object();
+    private int[] a11yIds = new int[0];
+    private int[] a11yParents = new int[0];
+    private int[] a11yBounds = new int[0];
+    private int[] a11yFlags = new int[0];
+    private String[] a11yClassNames = new String[0];
+    private String[] a11yTexts = new String[0];
+    private String[] a11yDescriptions = new String[0];
+    private int a11yFocusedId = AccessibilityNodeInfo.UNDEFINED_ITEM_ID;
+
+    public boolean isAccessibilityEnabled() {
+        AccessibilityManager manager = (AccessibilityManager) getSystemService(Context.ACCESSIBILITY_SERVICE);
+        return manager != null && manager.isEnabled();
+    }
+
+    public void setAccessibilityNodes(int[] ids, int[] parents, int[] bounds, int[] flags,
+            String[] classNames, String[] texts, String[] descriptions) {
+        synchronized (a11yLock) {
+            a11yIds = ids;
+            a11yParents = parents;
+            a11yBounds = bounds;
+            a11yFlags = flags;
+            a11yClassNames = classNames;
+            a11yTexts = texts;
+            a11yDescriptions = descriptions;
+        }
+        runOnUiThread(() -> {
+            if (mSurfaceView != null) {
+                AccessibilityEvent event = AccessibilityEvent.obtain(AccessibilityEvent.TYPE_WINDOW_CONTENT_CHANGED);
+                event.setSource(mSurfaceView);
+                mSurfaceView.getParent().requestSendAccessibilityEvent(mSurfaceView, event);
+            }
+        });
+    }
+
+    private class JanoNodeProvider extends AccessibilityNodeProvider {
+        @Override
+        public AccessibilityNodeInfo createAccessibilityNodeInfo(int virtualViewId) {
+            View host = mSurfaceView;
+            synchronized (a11yLock) {
+                if (virtualViewId == View.NO_ID) {
+                    AccessibilityNodeInfo info = AccessibilityNodeInfo.obtain(host);
+                    host.onInitializeAccessibilityNodeInfo(info);
+                    for (int i = 0; i < a11yIds.length; i++) {
+                        if (a11yParents[i] == View.NO_ID) {
+                            info.addChild(host, a11yIds[i]);
+                        }
+                    }
+                    return info;
+                }
+
+                int idx = -1;
+                for (int i = 0; i < a11yIds.length; i++) {
+                    if (a11yIds[i] == virtualViewId) {
+                        idx = i;
+                        break;
+                    }
+                }
+                if (idx < 0) {
+                    return null;
+                }
+
+                AccessibilityNodeInfo info = AccessibilityNodeInfo.obtain(host, virtualViewId);
+                info.setPackageName(getPackageName());
+                info.setClassName(a11yClassNames[idx]);
+                if (a11yParents[idx] == View.NO_ID) {
+                    info.setParent(host);
+                } else {
+                    info.setParent(host, a11yParents[idx]);
+                }
+                for (int i = 0; i < a11yIds.length; i++) {
+                    if (a11yParents[i] == virtualViewId) {
+                        info.addChild(host, a11yIds[i]);
+                    }
+                }
+
+                Rect bounds = new Rect(a11yBounds[idx * 4], a11yBounds[idx * 4 + 1],
+                        a11yBounds[idx * 4 + 2], a11yBounds[idx * 4 + 3]);
+                int[] hostLocation = new int[2];
+                host.getLocationOnScreen(hostLocation);
+                bounds.offset(hostLocation[0], hostLocation[1]);
+                info.setBoundsInScreen(bounds);
+                info.setVisibleToUser(true);
+
+                if (!a11yTexts[idx].isEmpty()) {
+                    info.setText(a11yTexts[idx]);
+                }
+                if (!a11yDescriptions[idx].isEmpty()) {
+                    info.setContentDescription(a11yDescriptions[idx]);
+                }
+
+                int flags = a11yFlags[idx];
+                info.setEnabled((flags & A11Y_ENABLED) != 0);
+                info.setFocusable((flags & A11Y_FOCUSABLE) != 0);
+                info.setFocused((flags & A11Y_FOCUSED) != 0);
+                info.setClickable((flags & A11Y_CLICKABLE) != 0);
+                info.setCheckable((flags & A11Y_CHECKABLE) != 0);
+                info.setChecked((flags & A11Y_CHECKED) != 0);
+                info.setEditable((flags & A11Y_EDITABLE) != 0);
+                info.setScrollable((flags & A11Y_ADJUSTABLE) != 0);
+                info.setPassword((flags & A11Y_PASSWORD) != 0);
+                if ((flags & A11Y_FOCUSABLE) != 0) {
+                    info.addAction(AccessibilityNodeInfo.ACTION_FOCUS);
+                }
+                if ((flags & A11Y_CLICKABLE) != 0) {
+                    info.addAction(AccessibilityNodeInfo.ACTION_CLICK);
+                }
+                if ((flags & A11Y_EDITABLE) != 0) {
+                    info.addAction(AccessibilityNodeInfo.ACTION_SET_TEXT);
+                }
+                if ((flags & A11Y_ADJUSTABLE) != 0) {
+                    info.addAction(AccessibilityNodeInfo.ACTION_SCROLL_FORWARD);
+                    info.addAction(AccessibilityNodeInfo.ACTION_SCROLL_BACKWARD);
+                }
+                info.setAccessibilityFocused(a11yFocusedId == virtualViewId);
+                if (a11yFocusedId == virtualViewId) {
+                    info.addAction(AccessibilityNodeInfo.ACTION_CLEAR_ACCESSIBILITY_FOCUS);
+                } else {
+                    info.addAction(AccessibilityNodeInfo.ACTION_ACCESSIBILITY_FOCUS);
+                }
+                return info;
+            }
+        }
+
+        @Override
+        public boolean performAction(int virtualViewId, int action, Bundle arguments) {
+            View host = mSurfaceView;
+            if (virtualViewId == View.NO_ID) {
+                return host.performAccessibilityAction(action, arguments);
+            }
+            switch (action) {
+                case AccessibilityNodeInfo.ACTION_ACCESSIBILITY_FOCUS:
+                    a11yFocusedId = virtualViewId;
+                    host.invalidate();
+                    sendEventForVirtualView(virtualViewId, AccessibilityEvent.TYPE_VIEW_ACCESSIBILITY_FOCUSED);
+                    return true;
+                case AccessibilityNodeInfo.ACTION_CLEAR_ACCESSIBILITY_FOCUS:
+                    if (a11yFocusedId == virtualViewId) {
+                        a11yFocusedId = AccessibilityNodeInfo.UNDEFINED_ITEM_ID;
+                    }
+                    host.invalidate();
+                    sendEventForVirtualView(virtualViewId, AccessibilityEvent.TYPE_VIEW_ACCESSIBILITY_FOCUS_CLEARED);
+                    return true;
+                default:
+                    String text = null;
+                    if (action == AccessibilityNodeInfo.ACTION_SET_TEXT && arguments != null) {
+                        CharSequence chars = arguments.getCharSequence(
+                                AccessibilityNodeInfo.ACTION_ARGUMENT_SET_TEXT_CHARSEQUENCE);
+                        text = chars == null ? "" : chars.toString();
+                    }
+                    onAccessibilityAction(virtualViewId, action, text);
+                    return true;
+            }
+        }
+
+        private void sendEventForVirtualView(int virtualViewId, int eventType) {
+            View host = mSurfaceView;
+            AccessibilityEvent event = AccessibilityEvent.obtain(eventType);
+            event.setPackageName(getPackageName());
+            event.setSource(host, virtualViewId);
+            host.getParent().requestSendAccessibilityEvent(host, event);
+        }
+    }
+
+    private void createAccessibilityProvider() {
+        // Exposes the egui widgets to accessibility services (eg: TalkBack) as virtual views.
+        JanoNodeProvider provider = new JanoNodeProvider();
+        mSurfaceView.setAccessibilityDelegate(new View.AccessibilityDelegate() {
+            @Override
+            public AccessibilityNodeProvider getAccessibilityNodeProvider(View host) {
+                return provider;
+            }
+        });
+    }
+
     @Override
     protected void onCreate(Bundle savedInstanceState) {
         // When true, the app will fit inside any system UI windows.
         // When false, we render behind any system UI windows.
         // WindowCompat.setDecorFitsSystemWindows(this.getWindow(), true);
         createInsetsListener();
+        createGamepadListener();
+        createClipboardListener();
         super.onCreate(savedInstanceState);
+        createAccessibilityProvider();
     }
 
     protected void onResume() {
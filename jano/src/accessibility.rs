@@ -0,0 +1,656 @@
+//! Exposes egui's AccessKit tree to TalkBack through the `AccessibilityNodeProvider` in MainActivity.
+
+use super::egui;
+#[cfg(target_os = "android")]
+use crate::{call_activity, with_activity};
+use egui::accesskit::{Action, ActionData, ActionRequest, Checked, Node, NodeId, Role, TreeUpdate};
+
+use std::collections::{HashMap, HashSet};
+#[cfg(target_os = "android")]
+use std::sync::Mutex;
+
+/// The virtual view id Android uses for the view hosting the virtual nodes.
+pub const HOST_VIEW_ID: i32 = -1;
+
+// Flags of `AccessibilityNode`, must match the `A11Y_*` constants in MainActivity.java.
+pub const A11Y_ENABLED: u32 = 1 << 0;
+pub const A11Y_FOCUSABLE: u32 = 1 << 1;
+pub const A11Y_FOCUSED: u32 = 1 << 2;
+pub const A11Y_CLICKABLE: u32 = 1 << 3;
+pub const A11Y_CHECKABLE: u32 = 1 << 4;
+pub const A11Y_CHECKED: u32 = 1 << 5;
+pub const A11Y_EDITABLE: u32 = 1 << 6;
+pub const A11Y_ADJUSTABLE: u32 = 1 << 7;
+pub const A11Y_PASSWORD: u32 = 1 << 8;
+
+// Actions of `android.view.accessibility.AccessibilityNodeInfo`.
+const ACTION_FOCUS: i32 = 0x1;
+const ACTION_CLICK: i32 = 0x10;
+const ACTION_SCROLL_FORWARD: i32 = 0x1000;
+const ACTION_SCROLL_BACKWARD: i32 = 0x2000;
+const ACTION_SET_TEXT: i32 = 0x200000;
+
+/// A node as presented to Android's accessibility services.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessibilityNode {
+    pub virtual_id: i32,
+    /// The `virtual_id` of the parent, or `HOST_VIEW_ID` for top-level nodes.
+    pub parent: i32,
+    /// (left, top, right, bottom) in pixels, relative to the window.
+    pub bounds: [i32; 4],
+    /// The Android widget class (eg: `android.widget.Button`) TalkBack describes the node as.
+    pub class_name: &'static str,
+    pub text: String,
+    pub content_description: String,
+    pub flags: u32,
+}
+
+/// The AccessKit tree sent by egui, with the stable `int` ids Android needs for virtual views.
+#[derive(Clone, Debug, Default)]
+pub struct AccessibilityTree {
+    root: Option<NodeId>,
+    focus: Option<NodeId>,
+    nodes: HashMap<NodeId, Node>,
+    virtual_ids: HashMap<NodeId, i32>,
+    node_ids: HashMap<i32, NodeId>,
+    next_virtual_id: i32,
+}
+impl AccessibilityTree {
+    pub fn update(&mut self, update: &TreeUpdate) {
+        if let Some(tree) = &update.tree {
+            self.root = Some(tree.root);
+        }
+        for (id, node) in &update.nodes {
+            self.nodes.insert(*id, node.clone());
+        }
+        self.focus = Some(update.focus);
+
+        // Forget the nodes that aren't in the tree anymore
+        let reachable = self.reachable();
+        self.nodes.retain(|id, _| reachable.contains(id));
+        self.virtual_ids.retain(|id, _| reachable.contains(id));
+        self.node_ids.retain(|_, id| reachable.contains(id));
+        for id in reachable {
+            if self.virtual_ids.contains_key(&id) || Some(id) == self.root {
+                continue;
+            }
+            let virtual_id = self.next_virtual_id;
+            self.next_virtual_id += 1;
+            self.virtual_ids.insert(id, virtual_id);
+            self.node_ids.insert(virtual_id, id);
+        }
+    }
+
+    fn reachable(&self) -> HashSet<NodeId> {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<_> = self.root.into_iter().collect();
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.nodes.get(&id) else {
+                continue;
+            };
+            if reachable.insert(id) {
+                stack.extend_from_slice(node.children());
+            }
+        }
+        reachable
+    }
+
+    /// The nodes of the tree in depth-first order, without the root window.
+    /// Bounds are converted from points to pixels with `scale_factor`.
+    pub fn nodes(&self, scale_factor: f32) -> Vec<AccessibilityNode> {
+        let mut out = Vec::new();
+        if let Some(root) = self.root.and_then(|id| self.nodes.get(&id)) {
+            for child in root.children() {
+                self.push_node(*child, HOST_VIEW_ID, scale_factor, &mut out);
+            }
+        }
+        out
+    }
+
+    fn push_node(
+        &self,
+        id: NodeId,
+        parent: i32,
+        scale_factor: f32,
+        out: &mut Vec<AccessibilityNode>,
+    ) {
+        let (Some(node), Some(&virtual_id)) = (self.nodes.get(&id), self.virtual_ids.get(&id))
+        else {
+            return;
+        };
+        // Text runs inside text fields are described by the field itself
+        if node.role() == Role::InlineTextBox {
+            return;
+        }
+
+        let bounds = node.bounds().map_or([0; 4], |rect| {
+            let scale = scale_factor as f64;
+            [
+                (rect.x0 * scale).round() as i32,
+                (rect.y0 * scale).round() as i32,
+                (rect.x1 * scale).round() as i32,
+                (rect.y1 * scale).round() as i32,
+            ]
+        });
+
+        let name = node.name().unwrap_or_default().to_string();
+        let value = match (node.value(), node.numeric_value()) {
+            (Some(value), _) => Some(value.to_string()),
+            (None, Some(value)) => Some(value.to_string()),
+            (None, None) => None,
+        };
+        let (text, content_description) = match value {
+            Some(value) => (value, name),
+            None => (name, String::new()),
+        };
+
+        out.push(AccessibilityNode {
+            virtual_id,
+            parent,
+            bounds,
+            class_name: class_name(node.role()),
+            text,
+            content_description,
+            flags: self.flags(id, node),
+        });
+        for child in node.children() {
+            self.push_node(*child, virtual_id, scale_factor, out);
+        }
+    }
+
+    fn flags(&self, id: NodeId, node: &Node) -> u32 {
+        let role = node.role();
+        let mut flags = 0;
+        let mut set = |flag, cond| {
+            if cond {
+                flags |= flag;
+            }
+        };
+        set(A11Y_ENABLED, !node.is_disabled());
+        set(A11Y_FOCUSABLE, node.supports_action(Action::Focus));
+        set(A11Y_FOCUSED, Some(id) == self.focus);
+        set(A11Y_CLICKABLE, node.default_action_verb().is_some());
+        set(A11Y_CHECKABLE, node.checked().is_some());
+        set(A11Y_CHECKED, node.checked() == Some(Checked::True));
+        set(
+            A11Y_EDITABLE,
+            matches!(
+                role,
+                Role::TextInput
+                    | Role::MultilineTextInput
+                    | Role::PasswordInput
+                    | Role::SearchInput
+            ),
+        );
+        set(
+            A11Y_ADJUSTABLE,
+            matches!(role, Role::Slider | Role::SpinButton),
+        );
+        set(A11Y_PASSWORD, role == Role::PasswordInput);
+        flags
+    }
+
+    /// Converts an action TalkBack performed on a virtual view into an AccessKit request for egui.
+    pub fn action_request(
+        &self,
+        virtual_id: i32,
+        android_action: i32,
+        text: Option<String>,
+    ) -> Option<ActionRequest> {
+        let target = *self.node_ids.get(&virtual_id)?;
+        let (action, data) = match android_action {
+            ACTION_CLICK => (Action::Default, None),
+            ACTION_FOCUS => (Action::Focus, None),
+            ACTION_SCROLL_FORWARD => (Action::Increment, None),
+            ACTION_SCROLL_BACKWARD => (Action::Decrement, None),
+            ACTION_SET_TEXT => (
+                Action::SetValue,
+                Some(ActionData::Value(text.unwrap_or_default().into())),
+            ),
+            _ => return None,
+        };
+        Some(ActionRequest {
+            action,
+            target,
+            data,
+        })
+    }
+}
+
+fn class_name(role: Role) -> &'static str {
+    match role {
+        Role::Button | Role::DefaultButton => "android.widget.Button",
+        Role::ToggleButton => "android.widget.ToggleButton",
+        Role::CheckBox => "android.widget.CheckBox",
+        Role::RadioButton => "android.widget.RadioButton",
+        Role::TextInput
+        | Role::MultilineTextInput
+        | Role::PasswordInput
+        | Role::SearchInput
+        | Role::SpinButton => "android.widget.EditText",
+        Role::Slider => "android.widget.SeekBar",
+        Role::ProgressIndicator => "android.widget.ProgressBar",
+        Role::ComboBox => "android.widget.Spinner",
+        Role::Image => "android.widget.ImageView",
+        Role::StaticText | Role::Link => "android.widget.TextView",
+        _ => "android.view.View",
+    }
+}
+
+#[cfg(target_os = "android")]
+/// Actions performed by TalkBack, waiting to be sent to egui.
+static PENDING_ACTIONS: Mutex<Vec<(i32, i32, Option<String>)>> = Mutex::new(Vec::new());
+
+#[cfg(target_os = "android")]
+#[allow(dead_code)]
+#[allow(non_snake_case)]
+#[no_mangle]
+/// Called by the JVM when an accessibility service performs an action on a virtual view.
+pub extern "C" fn Java_nodomain_jano_MainActivity_onAccessibilityAction(
+    mut env: jni::JNIEnv,
+    _class: jni::objects::JObject,
+    virtual_id: jni::sys::jint,
+    action: jni::sys::jint,
+    text: jni::objects::JString,
+) {
+    let text = match text.is_null() {
+        true => None,
+        false => env
+            .get_string(&text)
+            .ok()
+            .map(|text| text.to_string_lossy().to_string()),
+    };
+    PENDING_ACTIONS
+        .lock()
+        .unwrap()
+        .push((virtual_id, action, text));
+    crate::wake_main_loop();
+}
+
+#[cfg(target_os = "android")]
+/// Keeps MainActivity's accessibility nodes in sync with egui.
+#[derive(Debug, Default)]
+pub struct AccessibilityBridge {
+    pub tree: AccessibilityTree,
+    sent_nodes: Vec<AccessibilityNode>,
+    enabled: bool,
+}
+#[cfg(target_os = "android")]
+impl AccessibilityBridge {
+    /// Whether an accessibility service (eg: TalkBack) is running, as of the last `refresh_enabled`.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn refresh_enabled(&mut self) {
+        match is_accessibility_enabled() {
+            Ok(enabled) => self.enabled = enabled,
+            Err(err) => log::warn!("{err}"),
+        }
+    }
+
+    /// Sends the actions TalkBack performed since the last call as egui events.
+    pub fn take_events(&mut self, mut out: impl FnMut(egui::Event)) {
+        let actions = std::mem::take(&mut *PENDING_ACTIONS.lock().unwrap());
+        for (virtual_id, action, text) in actions {
+            match self.tree.action_request(virtual_id, action, text) {
+                Some(request) => out(egui::Event::AccessKitActionRequest(request)),
+                None => log::warn!("Unhandled accessibility action {action} on {virtual_id}"),
+            }
+        }
+    }
+
+    /// Applies `update` and sends the resulting nodes to MainActivity if they changed.
+    pub fn update(&mut self, update: &TreeUpdate, scale_factor: f32) {
+        self.tree.update(update);
+        let nodes = self.tree.nodes(scale_factor);
+        if nodes == self.sent_nodes {
+            return;
+        }
+        match send_nodes(&nodes) {
+            Ok(()) => self.sent_nodes = nodes,
+            Err(err) => log::warn!("{err}"),
+        }
+    }
+}
+
+#[cfg(target_os = "android")]
+fn is_accessibility_enabled() -> Result<bool, String> {
+    call_activity(
+        "isAccessibilityEnabled",
+        "()Z",
+        &[],
+        |_, value| match value {
+            jni::objects::JValueGen::Bool(enabled) => Ok(enabled != 0),
+            _ => Err(
+                "Java function MainActivity.isAccessibilityEnabled() returned non-boolean value"
+                    .into(),
+            ),
+        },
+    )
+}
+
+#[cfg(target_os = "android")]
+fn int_array<'local>(
+    env: &mut jni::JNIEnv<'local>,
+    values: &[i32],
+) -> jni::errors::Result<jni::objects::JIntArray<'local>> {
+    let array = env.new_int_array(values.len() as i32)?;
+    env.set_int_array_region(&array, 0, values)?;
+    Ok(array)
+}
+
+#[cfg(target_os = "android")]
+fn string_array<'local, 'a>(
+    env: &mut jni::JNIEnv<'local>,
+    values: impl ExactSizeIterator<Item = &'a str>,
+) -> jni::errors::Result<jni::objects::JObjectArray<'local>> {
+    let array = env.new_object_array(
+        values.len() as i32,
+        "java/lang/String",
+        jni::objects::JObject::null(),
+    )?;
+    for (i, value) in values.enumerate() {
+        let value = env.new_string(value)?;
+        env.set_object_array_element(&array, i as i32, &value)?;
+        env.delete_local_ref(value)?;
+    }
+    Ok(array)
+}
+
+#[cfg(target_os = "android")]
+fn send_nodes(nodes: &[AccessibilityNode]) -> Result<(), String> {
+    use jni::objects::JValue;
+
+    let ids: Vec<i32> = nodes.iter().map(|node| node.virtual_id).collect();
+    let parents: Vec<i32> = nodes.iter().map(|node| node.parent).collect();
+    let bounds: Vec<i32> = nodes.iter().flat_map(|node| node.bounds).collect();
+    let flags: Vec<i32> = nodes.iter().map(|node| node.flags as i32).collect();
+
+    with_activity(|env, activity| {
+        let err = |err| format!("Failed to send accessibility nodes to MainActivity : {err:?}");
+        let ids = int_array(env, &ids).map_err(err)?;
+        let parents = int_array(env, &parents).map_err(err)?;
+        let bounds = int_array(env, &bounds).map_err(err)?;
+        let flags = int_array(env, &flags).map_err(err)?;
+        let class_names =
+            string_array(env, nodes.iter().map(|node| node.class_name)).map_err(err)?;
+        let texts = string_array(env, nodes.iter().map(|node| node.text.as_str())).map_err(err)?;
+        let descriptions = string_array(
+            env,
+            nodes.iter().map(|node| node.content_description.as_str()),
+        )
+        .map_err(err)?;
+
+        match env.call_method(
+            activity,
+            "setAccessibilityNodes",
+            "([I[I[I[I[Ljava/lang/String;[Ljava/lang/String;[Ljava/lang/String;)V",
+            &[
+                JValue::Object(&ids),
+                JValue::Object(&parents),
+                JValue::Object(&bounds),
+                JValue::Object(&flags),
+                JValue::Object(&class_names),
+                JValue::Object(&texts),
+                JValue::Object(&descriptions),
+            ],
+        ) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!(
+                "JNI call to MainActivity.setAccessibilityNodes() failed : {err:?}"
+            )),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::accesskit::{DefaultActionVerb, NodeBuilder, NodeClassSet, Rect, Tree};
+
+    const WINDOW: NodeId = NodeId(1);
+    const BUTTON: NodeId = NodeId(2);
+    const FIELD: NodeId = NodeId(3);
+    const FIELD_TEXT: NodeId = NodeId(4);
+    const GROUP: NodeId = NodeId(5);
+    const CHECKBOX: NodeId = NodeId(6);
+    const LABEL: NodeId = NodeId(7);
+
+    fn node(
+        classes: &mut NodeClassSet,
+        role: Role,
+        name: &str,
+        bounds: Rect,
+        setup: impl FnOnce(&mut NodeBuilder),
+    ) -> Node {
+        let mut builder = NodeBuilder::new(role);
+        builder.set_name(name);
+        builder.set_bounds(bounds);
+        setup(&mut builder);
+        builder.build(classes)
+    }
+
+    fn window(classes: &mut NodeClassSet, children: Vec<NodeId>) -> (NodeId, Node) {
+        let bounds = Rect::new(0.0, 0.0, 400.0, 800.0);
+        let window = node(classes, Role::Window, "", bounds, |b| {
+            b.set_children(children)
+        });
+        (WINDOW, window)
+    }
+
+    fn sample_update() -> TreeUpdate {
+        let mut classes = NodeClassSet::new();
+        let c = &mut classes;
+        TreeUpdate {
+            nodes: vec![
+                window(c, vec![BUTTON, FIELD, GROUP]),
+                (
+                    BUTTON,
+                    node(
+                        c,
+                        Role::Button,
+                        "OK",
+                        Rect::new(10.0, 20.0, 110.5, 60.0),
+                        |b| {
+                            b.add_action(Action::Focus);
+                            b.set_default_action_verb(DefaultActionVerb::Click);
+                        },
+                    ),
+                ),
+                (
+                    FIELD,
+                    node(
+                        c,
+                        Role::TextInput,
+                        "Name",
+                        Rect::new(10.0, 70.0, 210.0, 100.0),
+                        |b| {
+                            b.add_action(Action::Focus);
+                            b.set_value("Ferris");
+                            b.set_children(vec![FIELD_TEXT]);
+                        },
+                    ),
+                ),
+                (
+                    FIELD_TEXT,
+                    node(
+                        c,
+                        Role::InlineTextBox,
+                        "Ferris",
+                        Rect::new(12.0, 72.0, 60.0, 98.0),
+                        |_| {},
+                    ),
+                ),
+                (
+                    GROUP,
+                    node(
+                        c,
+                        Role::Group,
+                        "",
+                        Rect::new(0.0, 110.0, 400.0, 200.0),
+                        |b| {
+                            b.set_children(vec![CHECKBOX]);
+                        },
+                    ),
+                ),
+                (
+                    CHECKBOX,
+                    node(
+                        c,
+                        Role::CheckBox,
+                        "Dark mode",
+                        Rect::new(10.0, 120.0, 150.0, 140.0),
+                        |b| {
+                            b.set_checked(Checked::True);
+                            b.set_disabled();
+                        },
+                    ),
+                ),
+            ],
+            tree: Some(Tree::new(WINDOW)),
+            focus: BUTTON,
+        }
+    }
+
+    fn find<'a>(nodes: &'a [AccessibilityNode], content: &str) -> &'a AccessibilityNode {
+        nodes
+            .iter()
+            .find(|node| node.text == content || node.content_description == content)
+            .unwrap_or_else(|| panic!("no node for {content:?} in {nodes:?}"))
+    }
+
+    #[test]
+    fn nodes_of_a_tree() {
+        let mut tree = AccessibilityTree::default();
+        tree.update(&sample_update());
+        let nodes = tree.nodes(2.0);
+
+        // Neither the root window nor the text runs of the field
+        assert_eq!(nodes.len(), 4);
+        let virtual_ids: HashSet<_> = nodes.iter().map(|node| node.virtual_id).collect();
+        assert_eq!(virtual_ids.len(), 4);
+        assert!(!virtual_ids.contains(&HOST_VIEW_ID));
+
+        let button = find(&nodes, "OK");
+        assert_eq!(button.parent, HOST_VIEW_ID);
+        assert_eq!(button.bounds, [20, 40, 221, 120]);
+        assert_eq!(button.class_name, "android.widget.Button");
+        assert_eq!(
+            button.flags,
+            A11Y_ENABLED | A11Y_FOCUSABLE | A11Y_FOCUSED | A11Y_CLICKABLE
+        );
+
+        // The value is the text, the name describes it
+        let field = find(&nodes, "Ferris");
+        assert_eq!(field.parent, HOST_VIEW_ID);
+        assert_eq!(field.content_description, "Name");
+        assert_eq!(field.class_name, "android.widget.EditText");
+        assert_eq!(field.flags, A11Y_ENABLED | A11Y_FOCUSABLE | A11Y_EDITABLE);
+
+        let group = nodes
+            .iter()
+            .find(|node| node.class_name == "android.view.View");
+        let group = group.expect("group node");
+        assert_eq!(group.parent, HOST_VIEW_ID);
+        let checkbox = find(&nodes, "Dark mode");
+        assert_eq!(checkbox.parent, group.virtual_id);
+        assert_eq!(checkbox.bounds, [20, 240, 300, 280]);
+        assert_eq!(checkbox.flags, A11Y_CHECKABLE | A11Y_CHECKED);
+
+        // Depth-first order
+        let position = |virtual_id| nodes.iter().position(|n| n.virtual_id == virtual_id);
+        assert_eq!(
+            position(group.virtual_id).unwrap() + 1,
+            position(checkbox.virtual_id).unwrap()
+        );
+    }
+
+    #[test]
+    fn action_requests() {
+        let mut tree = AccessibilityTree::default();
+        tree.update(&sample_update());
+        let nodes = tree.nodes(1.0);
+        let (button, field) = (find(&nodes, "OK"), find(&nodes, "Ferris"));
+
+        let click = tree.action_request(button.virtual_id, ACTION_CLICK, None);
+        assert_eq!(
+            click,
+            Some(ActionRequest {
+                action: Action::Default,
+                target: BUTTON,
+                data: None,
+            })
+        );
+        let set_text = tree.action_request(field.virtual_id, ACTION_SET_TEXT, Some("Crab".into()));
+        assert_eq!(
+            set_text,
+            Some(ActionRequest {
+                action: Action::SetValue,
+                target: FIELD,
+                data: Some(ActionData::Value("Crab".into())),
+            })
+        );
+        let focus = tree.action_request(field.virtual_id, ACTION_FOCUS, None);
+        assert_eq!(focus.map(|r| r.action), Some(Action::Focus));
+        let scroll = tree.action_request(field.virtual_id, ACTION_SCROLL_FORWARD, None);
+        assert_eq!(scroll.map(|r| r.action), Some(Action::Increment));
+
+        // Unsupported actions, the host view and unknown ids
+        assert_eq!(tree.action_request(button.virtual_id, 0x4, None), None);
+        assert_eq!(tree.action_request(HOST_VIEW_ID, ACTION_CLICK, None), None);
+        assert_eq!(tree.action_request(1000, ACTION_CLICK, None), None);
+    }
+
+    #[test]
+    fn virtual_ids_are_stable_across_updates() {
+        let mut tree = AccessibilityTree::default();
+        tree.update(&sample_update());
+        let before = tree.nodes(1.0);
+        let id_of = |nodes: &[AccessibilityNode], content| find(nodes, content).virtual_id;
+
+        // A partial update renaming the button and moving the focus keeps every id
+        let mut classes = NodeClassSet::new();
+        let bounds = Rect::new(10.0, 20.0, 110.5, 60.0);
+        let button = node(&mut classes, Role::Button, "Okay", bounds, |_| {});
+        tree.update(&TreeUpdate {
+            nodes: vec![(BUTTON, button)],
+            tree: None,
+            focus: FIELD,
+        });
+        let after = tree.nodes(1.0);
+        assert_eq!(id_of(&after, "Okay"), id_of(&before, "OK"));
+        assert_eq!(id_of(&after, "Ferris"), id_of(&before, "Ferris"));
+        assert_eq!(id_of(&after, "Dark mode"), id_of(&before, "Dark mode"));
+        assert_eq!(find(&after, "Ferris").flags & A11Y_FOCUSED, A11Y_FOCUSED);
+        assert_eq!(find(&after, "Okay").flags & A11Y_FOCUSED, 0);
+
+        // Removing the group forgets it and its child, new nodes get ids that were never used
+        let label_bounds = Rect::new(10.0, 110.0, 200.0, 130.0);
+        let label = node(
+            &mut classes,
+            Role::StaticText,
+            "Hello",
+            label_bounds,
+            |_| {},
+        );
+        tree.update(&TreeUpdate {
+            nodes: vec![
+                window(&mut classes, vec![BUTTON, FIELD, LABEL]),
+                (LABEL, label),
+            ],
+            tree: None,
+            focus: FIELD,
+        });
+        let removed = id_of(&before, "Dark mode");
+        assert_eq!(tree.action_request(removed, ACTION_CLICK, None), None);
+        let last = tree.nodes(1.0);
+        assert_eq!(last.len(), 3);
+        assert_eq!(id_of(&last, "Okay"), id_of(&before, "OK"));
+        let label = find(&last, "Hello");
+        assert!(before
+            .iter()
+            .all(|node| node.virtual_id != label.virtual_id));
+        assert_eq!(label.class_name, "android.widget.TextView");
+    }
+}
@@ -0,0 +1,183 @@
+//! Access to the Android clipboard through MainActivity's `ClipboardManager`.
+
+use crate::{call_activity, with_activity, Picture};
+use jni::objects::{JObject, JPrimitiveArray, JString, JValue, JValueOwned};
+use jni::JNIEnv;
+
+use glam::uvec2;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when the primary clip changes, until `take_changed` is called.
+static CLIPBOARD_CHANGED: AtomicBool = AtomicBool::new(false);
+
+#[allow(dead_code)]
+#[allow(non_snake_case)]
+#[no_mangle]
+/// Called by the JVM when the primary clip changes (in this app or another one).
+pub extern "C" fn Java_nodomain_jano_MainActivity_onClipboardChanged(
+    _env: JNIEnv,
+    _class: JObject,
+) {
+    CLIPBOARD_CHANGED.store(true, Ordering::Relaxed);
+    crate::wake_main_loop();
+}
+
+/// Whether the clipboard changed since the last call.
+/// `android_main` calls this every frame to notify `AppState::on_clipboard_changed`.
+pub fn take_changed() -> bool {
+    CLIPBOARD_CHANGED.swap(false, Ordering::Relaxed)
+}
+
+/// Converts a `String` returned by Java (possibly null) into a rust String.
+fn get_string(env: &mut JNIEnv, value: JValueOwned, name: &str) -> Result<Option<String>, String> {
+    let Ok(object) = value.l() else {
+        Err(format!(
+            "Java function MainActivity.{name}() returned non-Object value"
+        ))?
+    };
+    if object.is_null() {
+        return Ok(None);
+    }
+    match env.get_string(&JString::from(object)) {
+        Ok(s) => Ok(Some(s.to_string_lossy().to_string())),
+        Err(err) => Err(format!(
+            "Failed to read String from MainActivity.{name}() : {err:?}"
+        )),
+    }
+}
+
+fn get_text_item(name: &str) -> Result<Option<String>, String> {
+    call_activity(name, "()Ljava/lang/String;", &[], |env, value| {
+        get_string(env, value, name)
+    })
+}
+
+fn set_items(name: &str, values: &[&str]) -> Result<(), String> {
+    with_activity(|env, activity| {
+        let mut java_values = Vec::with_capacity(values.len());
+        for value in values {
+            match env.new_string(value) {
+                Ok(v) => java_values.push(v),
+                Err(_) => Err(String::from("JNI jstring creation failed"))?,
+            }
+        }
+        let args: Vec<JValue> = java_values.iter().map(|v| v.into()).collect();
+        let sig = format!("({})V", "Ljava/lang/String;".repeat(values.len()));
+        match env.call_method(activity, name, sig, &args) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!(
+                "JNI call to MainActivity.{name}() failed : {err:?}"
+            )),
+        }
+    })
+}
+
+/// The MIME types of the primary clip (eg: `text/plain`, `text/html`, `image/png`), empty if there is none.
+pub fn mime_types() -> Result<Vec<String>, String> {
+    let name = "getClipboardMimeTypes";
+    call_activity(name, "()[Ljava/lang/String;", &[], |env, value| {
+        let Ok(object) = value.l() else {
+            Err(format!(
+                "Java function MainActivity.{name}() returned non-Object value"
+            ))?
+        };
+        let array = jni::objects::JObjectArray::from(object);
+        let err = |err| format!("Failed to read array from MainActivity.{name}() : {err:?}");
+        let len = env.get_array_length(&array).map_err(err)?;
+        let mut types = Vec::with_capacity(len as usize);
+        for i in 0..len {
+            let element = env.get_object_array_element(&array, i).map_err(err)?;
+            if let Some(mime_type) = get_string(env, JValueOwned::Object(element), name)? {
+                types.push(mime_type);
+            }
+        }
+        Ok(types)
+    })
+}
+
+/// The primary clip as plain text (HTML and URIs are converted to text), `None` if the clipboard is empty.
+pub fn get_text() -> Result<Option<String>, String> {
+    get_text_item("getClipboardText")
+}
+
+/// The HTML of the primary clip, `None` if it doesn't contain HTML.
+pub fn get_html() -> Result<Option<String>, String> {
+    get_text_item("getClipboardHtml")
+}
+
+/// The URI of the primary clip (eg: a `content://` URI to a copied file), `None` if it doesn't contain one.
+pub fn get_uri() -> Result<Option<String>, String> {
+    get_text_item("getClipboardUri")
+}
+
+/// The image the primary clip's URI points to, `None` if it doesn't point to an image.
+pub fn get_image() -> Result<Option<Picture>, String> {
+    with_activity(|env, activity| {
+        let err = |err| format!("JNI call to MainActivity.getClipboardImage() failed : {err:?}");
+
+        let size_out = env.new_int_array(2).map_err(err)?;
+        let value = env
+            .call_method(
+                activity,
+                "getClipboardImage",
+                "([I)[B",
+                &[JValue::Object(&size_out)],
+            )
+            .map_err(err)?;
+        let Ok(object) = value.l() else {
+            Err("Java function MainActivity.getClipboardImage() returned non-Object value")?
+        };
+        if object.is_null() {
+            return Ok(None);
+        }
+        let j_arr = JPrimitiveArray::from(object);
+        let len = env.get_array_length(&j_arr).map_err(err)? as usize;
+        let mut data = vec![0i8; len];
+        env.get_byte_array_region(&j_arr, 0, &mut data)
+            .map_err(err)?;
+        let mut size = [0; 2];
+        env.get_int_array_region(&size_out, 0, &mut size)
+            .map_err(err)?;
+
+        Ok(Some(Picture {
+            data: data.into_iter().map(|b| b as u8).collect(),
+            size: uvec2(size[0] as u32, size[1] as u32),
+        }))
+    })
+}
+
+pub fn set_text(text: &str) -> Result<(), String> {
+    set_items("setClipboardText", &[text])
+}
+
+/// Copies `html`, with `text` as the plain text version for apps that don't support HTML.
+pub fn set_html(html: &str, text: &str) -> Result<(), String> {
+    set_items("setClipboardHtml", &[html, text])
+}
+
+pub fn set_uri(uri: &str) -> Result<(), String> {
+    set_items("setClipboardUri", &[uri])
+}
+
+/// Copies `pic` as a PNG, shared with other apps through the app's `FileProvider`.
+pub fn set_image(pic: &Picture) -> Result<(), String> {
+    with_activity(|env, activity| {
+        let err = |err| format!("JNI call to MainActivity.setClipboardImage() failed : {err:?}");
+        let data = env.byte_array_from_slice(&pic.data).map_err(err)?;
+        let args = [
+            JValue::Object(&data),
+            JValue::Int(pic.size.x as i32),
+            JValue::Int(pic.size.y as i32),
+        ];
+        let value = env
+            .call_method(activity, "setClipboardImage", "([BII)Z", &args)
+            .map_err(err)?;
+        match value.z() {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("MainActivity.setClipboardImage() failed to save the image".into()),
+            Err(err) => Err(format!(
+                "Java function MainActivity.setClipboardImage() returned non-boolean value : {err:?}"
+            )),
+        }
+    })
+}
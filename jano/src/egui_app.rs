@@ -1,74 +1,239 @@
 #[cfg(not(feature = "pollster"))]
-compile_error!("To use `egui_27` feature, you must also use `pollster` feature");
+compile_error!("To use `egui_27` or `egui_28` feature, you must also use `pollster` feature, use `egui-0.27` or `egui-0.28` instead");
 
 use super::{egui, wgpu};
 use crate::egui_wgpu;
-use crate::graphics::Gpu;
+use crate::gamepad::{GamepadButton, GamepadEvent, Gamepads};
+use crate::graphics::{Gpu, GpuConfig};
+use crate::system_fonts::SystemFonts;
+use crate::texture_cache::TextureCache;
 use crate::{
-    android, scale_factor, translate_input_event, AppState, FrameStats, Picture, PtrButton,
-    TouchEvent, TouchTranslater,
+    android, translate_input_event, AppState, FrameStats, ImeEvent, Modifiers, Picture, PtrButton,
+    TextFieldEdit, TouchEvent, TouchPhase, TouchTranslater,
 };
-use android_activity::MainEvent;
-use glam::{uvec2, vec2, UVec2};
+use android_activity::{input::Keycode, MainEvent};
+use glam::{vec2, UVec2};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub struct Egui {
     pub window: crate::Window,
     pub gpu: Gpu,
     pub ctx: egui::Context,
     pub renderer: egui_wgpu::Renderer,
-    pub texture_handles: std::collections::HashMap<egui::Id, egui::TextureHandle>,
+    pub textures: TextureCache,
+    // `TouchTranslater.gamepads`, lent to the app while it draws a frame.
+    gamepads: Option<Gamepads>,
+    density: f32,
+    scale_factor_override: Option<f32>,
+    // The font scale the text styles are scaled by.
+    font_scale: f32,
+    system_fonts: Option<SystemFonts>,
+    // When egui wants the next frame, set by its repaint callback (from any thread) and after each frame.
+    repaint_at: Arc<Mutex<Option<Instant>>>,
 }
 impl Egui {
+    /// Whether the window was terminated and no frames can be drawn until `resume`.
+    pub fn is_suspended(&self) -> bool {
+        self.gpu.is_suspended()
+    }
+
+    /// Stops drawing frames and drops the surface when the window is terminated, keeping the context, device and textures.
+    pub fn suspend(&mut self) {
+        self.gpu.suspend();
+    }
+
+    /// Draws frames again, to a surface created for the new `window`.
+    /// Fails if the surface isn't compatible with the renderer, `Egui` has to be recreated then.
+    pub fn resume(&mut self, window: &crate::Window) -> Result<(), String> {
+        self.gpu.resume(window)?;
+        self.window = window.clone();
+        self.refresh_density();
+        self.ctx.request_repaint();
+        Ok(())
+    }
+
+    /// The number of pixels per point: the display density, unless overridden with `set_scale_factor_override`.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor_override.unwrap_or(self.density)
+    }
+
+    /// Overrides the display density (eg: for an accessibility zoom), or goes back to it if `None`.
+    pub fn set_scale_factor_override(&mut self, factor: Option<f32>) {
+        self.scale_factor_override = factor.filter(|factor| *factor > 0.0);
+    }
+
+    /// Reads the display density again (eg: after the app was moved to another display).
+    pub fn refresh_density(&mut self) {
+        match crate::display_density() {
+            Ok(density) if density > 0.0 => {
+                if density != self.density {
+                    log::info!("Display density changed to {density}");
+                }
+                self.density = density;
+            }
+            Ok(density) => log::warn!("Ignoring invalid display density {density}"),
+            Err(err) => log::warn!("Failed to get display density: {err}"),
+        }
+    }
+
+    /// Whether egui wants a frame now (after input, for an animation or after `ctx.request_repaint()`).
+    pub fn needs_repaint(&self) -> bool {
+        self.repaint_at
+            .lock()
+            .unwrap()
+            .is_some_and(|at| at <= Instant::now())
+    }
+
+    /// How long until egui wants the next frame, `None` if it doesn't want one.
+    pub fn repaint_delay(&self) -> Option<Duration> {
+        self.repaint_at
+            .lock()
+            .unwrap()
+            .map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Applies the system's theme (dark or light visuals) and font scale (to all text styles) to egui's style.
+    /// Done by `EguiAppState` when the window is created and when the configuration changes, unless `EguiApp::use_system_style` returns false.
+    pub fn apply_system_style(&mut self) {
+        match crate::system_theme() {
+            Some(crate::Theme::Dark) => self.ctx.set_visuals(egui::Visuals::dark()),
+            Some(crate::Theme::Light) => self.ctx.set_visuals(egui::Visuals::light()),
+            None => {}
+        }
+        let font_scale = match crate::font_scale() {
+            Ok(scale) if scale > 0.0 => scale,
+            Ok(scale) => {
+                log::warn!("Ignoring invalid font scale {scale}");
+                return;
+            }
+            Err(err) => {
+                log::warn!("Failed to get font scale: {err}");
+                return;
+            }
+        };
+        if font_scale != self.font_scale {
+            // Relative to the current scale, to keep the app's own text styles
+            let factor = font_scale / self.font_scale;
+            self.ctx.style_mut(|style| {
+                for font in style.text_styles.values_mut() {
+                    font.size *= factor;
+                }
+            });
+            self.font_scale = font_scale;
+        }
+    }
+
+    /// Uses `definitions` as egui's fonts, with the device's fonts added when text in a script they don't cover
+    /// (eg: Japanese, Korean, Arabic) is typed or passed to `load_fonts_for`.
+    /// Color emoji fonts aren't loaded, egui can't draw them.
+    pub fn use_system_fonts(&mut self, definitions: egui::FontDefinitions) -> Result<(), String> {
+        let fonts = SystemFonts::from_system(definitions)?;
+        self.ctx.set_fonts(fonts.definitions.clone());
+        self.system_fonts = Some(fonts);
+        Ok(())
+    }
+
+    /// Loads the system fonts needed to show `text`, if `use_system_fonts` was called.
+    /// The fonts are used from the next frame on.
+    pub fn load_fonts_for(&mut self, text: &str) {
+        let Some(fonts) = &mut self.system_fonts else {
+            return;
+        };
+        if fonts.add_fonts_for(text) {
+            self.ctx.set_fonts(fonts.definitions.clone());
+            self.ctx.request_repaint();
+        }
+    }
+
+    /// The connected gamepads, while drawing a frame of an app whose `EguiApp::use_gamepads` returns true.
+    pub fn gamepads(&self) -> Option<&Gamepads> {
+        self.gamepads.as_ref()
+    }
+
+    pub fn gamepads_mut(&mut self) -> Option<&mut Gamepads> {
+        self.gamepads.as_mut()
+    }
+
+    /// Resources shared with `egui_wgpu::Callback` paint callbacks (eg: pipelines and buffers created by the app).
+    pub fn callback_resources(&mut self) -> &mut egui_wgpu::CallbackResources {
+        &mut self.renderer.callback_resources
+    }
+
+    /// The texture for `id`, loaded again from `pic` when `version` or the picture's size changes.
+    /// `version` is a cheap key that changes with the pixels, such as an edit counter or a file's modification time
+    /// (`TextureCache::load_pic` compares the pixels instead).
     pub fn obtain_tex_handle_for_pic(
         &mut self,
         id: impl Into<egui::Id>,
+        version: impl std::hash::Hash,
         pic: &Picture,
     ) -> egui::TextureHandle {
-        let id = id.into();
-        if let Some(handle) = self.texture_handles.get(&id) {
-            return handle.clone();
-        }
         let size = [pic.size.x as usize, pic.size.y as usize];
-        let img = egui::ColorImage::from_rgba_unmultiplied(size, &pic.data);
-        let debug_id = "auto-tex:obtain_tex_handle_for_pic";
-        let handle = self.ctx.load_texture(debug_id, img, Default::default());
-        self.texture_handles.insert(id, handle.clone());
-        handle
+        self.textures
+            .load_versioned(&self.ctx, id, (version, size), || {
+                egui::ColorImage::from_rgba_unmultiplied(size, &pic.data)
+            })
     }
+    /// Same as `obtain_tex_handle_for_pic`, for an egui image.
     pub fn obtain_tex_handle_for_img(
         &mut self,
         id: impl Into<egui::Id>,
+        version: impl std::hash::Hash,
         img: egui::ColorImage,
     ) -> egui::TextureHandle {
-        let id = id.into();
-        if let Some(handle) = self.texture_handles.get(&id) {
-            return handle.clone();
-        }
-        let debug_id = "auto-tex:obtain_tex_handle_for_img";
-        let handle = self.ctx.load_texture(debug_id, img, Default::default());
-        self.texture_handles.insert(id, handle.clone());
-        handle
-    }
-
-    pub async fn new(
-        window: crate::Window,
-        instance: wgpu::Instance,
-        surface: wgpu::Surface<'static>,
-        size: UVec2,
-    ) -> Self {
-        let gpu = Gpu::new(instance, surface, size).await;
+        self.textures
+            .load_versioned(&self.ctx, id, (version, img.size), || img)
+    }
+
+    pub async fn new(window: crate::Window, config: &GpuConfig) -> Result<Self, String> {
+        let gpu = Gpu::new(config, &window).await?;
         let ctx = egui::Context::default();
         let renderer = egui_wgpu::Renderer::new(&gpu.device, gpu.surface_config.format, None, 1);
-        Self {
+        let repaint_at = Arc::new(Mutex::new(Some(Instant::now())));
+        ctx.set_request_repaint_callback({
+            let repaint_at = repaint_at.clone();
+            let main_thread = std::thread::current().id();
+            move |info| {
+                if info.viewport_id != egui::ViewportId::ROOT {
+                    return;
+                }
+                schedule_repaint(&repaint_at, info.delay);
+                // The main thread checks `repaint_at` after each frame, other threads have to wake it up
+                if std::thread::current().id() != main_thread {
+                    crate::wake_main_loop();
+                }
+            }
+        });
+        let mut egui = Self {
             window,
             gpu,
             ctx,
             renderer,
-            texture_handles: Default::default(),
-        }
+            textures: Default::default(),
+            gamepads: None,
+            density: 1.0,
+            scale_factor_override: None,
+            font_scale: 1.0,
+            system_fonts: None,
+            repaint_at,
+        };
+        egui.refresh_density();
+        Ok(egui)
     }
 }
 
+/// Makes egui's next frame due in `delay`, unless one is already due sooner.
+fn schedule_repaint(repaint_at: &Mutex<Option<Instant>>, delay: Duration) {
+    // Duration::MAX means no repaint
+    let Some(at) = Instant::now().checked_add(delay) else {
+        return;
+    };
+    let mut repaint_at = repaint_at.lock().unwrap();
+    *repaint_at = Some(repaint_at.map_or(at, |prev| prev.min(at)));
+}
+
 pub struct EguiAppState<A>(pub EguiInput, pub Option<Egui>, pub A);
 impl<A> EguiAppState<A> {
     #[inline(always)]
@@ -83,7 +248,39 @@ pub trait EguiApp {
     fn on_save_state(&mut self) {}
 
     fn draw_frame(&mut self, egui: &mut Egui, ctx: &egui::Context, stats: FrameStats);
+    /// Records render passes drawing under the UI (eg: a 3D scene) to the surface's `view`, after `draw_frame`.
+    /// Return false if nothing was drawn, so that egui's render pass clears the surface.
+    /// To draw inside the UI instead, add an `egui_wgpu::Callback` paint callback to a `Painter`.
+    fn render_under_ui(
+        &mut self,
+        _egui: &mut Egui,
+        _encoder: &mut wgpu::CommandEncoder,
+        _view: &wgpu::TextureView,
+    ) -> bool {
+        false
+    }
+    /// Return true to draw every frame, instead of only after input or when egui asks for a repaint
+    /// (eg: for a game that animates without calling `ctx.request_repaint()`).
+    fn continuous_rendering(&self) -> bool {
+        false
+    }
+    /// Called when the surface was resized to the window's new `size` (in pixels), before the frame is drawn.
+    fn on_resized(&mut self, _egui: &mut Egui, _size: UVec2) {}
+    /// How the GPU used to draw egui is created.
+    fn gpu_config(&self) -> GpuConfig {
+        GpuConfig::default()
+    }
+    /// Return false to keep egui's style as the app sets it, instead of following the system's theme and font scale.
+    fn use_system_style(&self) -> bool {
+        true
+    }
+    /// Return true to track the connected gamepads, read with `Egui::gamepads` in `draw_frame`.
+    /// Their d-pad and A/B buttons also move the focus and click in the UI.
+    fn use_gamepads(&self) -> bool {
+        false
+    }
     fn on_picture_taken(&mut self, _egui: &Option<Egui>, _pic: Picture) {}
+    fn on_clipboard_changed(&mut self, _egui: &Option<Egui>) {}
 }
 
 impl<A: EguiApp> AppState for EguiAppState<A> {
@@ -98,33 +295,73 @@ impl<A: EguiApp> AppState for EguiAppState<A> {
             }
             MainEvent::Resume { .. } => {
                 log::info!("App resumed...");
-                crate::input::set_scale_factor(5.0);
+                #[cfg(feature = "accesskit")]
+                _input.accessibility.refresh_enabled();
                 app.on_resume();
                 *draw_frames = true;
+                if let Some(egui) = egui {
+                    egui.ctx.request_repaint();
+                }
             }
             MainEvent::InitWindow { .. } => {
                 log::info!("Window initialized - creating Surface...");
                 let window = crate::android().native_window();
 
+                if let (Some(win), Some(egui)) = (&window, egui.as_mut()) {
+                    match egui.resume(win) {
+                        Ok(()) => return false,
+                        Err(err) => log::warn!("Failed to keep egui: {err}"),
+                    }
+                }
                 if let Some(win) = window {
-                    let instance = wgpu::Instance::new(Default::default());
-                    let surface = crate::graphics::create_wgpu_surface(&instance, &win);
-
-                    let size = uvec2(win.width() as u32, win.height() as u32);
-                    *egui = Some(pollster::block_on(Egui::new(win, instance, surface, size)));
+                    match pollster::block_on(Egui::new(win, &app.gpu_config())) {
+                        Ok(mut new_egui) => {
+                            if app.use_system_style() {
+                                new_egui.apply_system_style();
+                            }
+                            *egui = Some(new_egui);
+                        }
+                        Err(err) => log::error!("Failed to create egui: {err}"),
+                    }
                 } else {
                     log::error!("native_window() returned None during InitWindow callback");
                 }
             }
             MainEvent::TerminateWindow { .. } => {
                 log::info!("App terminated...");
-                *egui = None;
+                // Only the surface is dropped, egui's state and textures are kept until the next InitWindow
+                if let Some(egui) = egui {
+                    egui.suspend();
+                }
+            }
+            MainEvent::WindowResized { .. } => {
+                log::info!("Window resized...");
+                if let Some(egui) = egui {
+                    egui.ctx.request_repaint();
+                }
+            }
+            MainEvent::RedrawNeeded { .. } => {
+                if let Some(egui) = egui {
+                    egui.ctx.request_repaint();
+                }
             }
-            MainEvent::WindowResized { .. } => log::info!("Window resized..."),
-            MainEvent::RedrawNeeded { .. } => {}
             MainEvent::InputAvailable { .. } => {}
-            MainEvent::ConfigChanged { .. } => {}
-            MainEvent::LowMemory => log::warn!("Recieved LowMemory Event..."),
+            MainEvent::ConfigChanged { .. } => {
+                if let Some(egui) = egui {
+                    egui.refresh_density();
+                    if app.use_system_style() {
+                        egui.apply_system_style();
+                    }
+                    egui.ctx.request_repaint();
+                }
+            }
+            MainEvent::LowMemory => {
+                log::warn!("Recieved LowMemory Event...");
+                if let Some(egui) = egui {
+                    log::info!("Freeing {} bytes of textures", egui.textures.bytes());
+                    egui.textures.clear();
+                }
+            }
             MainEvent::Destroy => {
                 log::info!("App destroyed...");
                 return true;
@@ -140,10 +377,24 @@ impl<A: EguiApp> AppState for EguiAppState<A> {
             log::warn!("Frame drawing canceled: egui is None");
             return;
         };
+        if egui.is_suspended() {
+            return;
+        }
+        egui.gpu.update_size();
+        if let Some(size) = egui.gpu.take_resized() {
+            app.on_resized(egui, size);
+        }
 
         // Handle input
         'i: {
-            input.translater.set_scale_factor(scale_factor());
+            input.translater.set_scale_factor(egui.scale_factor());
+            if let Some(gestures) = &mut input.translater.gestures {
+                let size = vec2(egui.window.width() as f32, egui.window.height() as f32);
+                gestures.set_screen_size(size);
+            }
+            if app.use_gamepads() && input.translater.gamepads.is_none() {
+                input.translater.gamepads = Some(Gamepads::new());
+            }
             input.update();
             let mut iter = match android().input_events_iter() {
                 Ok(iter) => iter,
@@ -158,41 +409,101 @@ impl<A: EguiApp> AppState for EguiAppState<A> {
                 })
             }) {}
         }
+        insert_pasted_text(&mut input.raw.events);
+        if let Some(edit) = input.translater.text_input.take_edit() {
+            input.apply_text_edit(&egui.ctx, edit);
+        }
+
+        // let screen_rect = {
+        //     let size = egui::vec2(egui.window.width() as f32, egui.window.height() as f32);
+        //     egui::Rect::from_min_size(egui::pos2(0.0, 0.0), size)
+        // };
+        let content_rect = {
+            let size = vec2(egui.window.width() as f32, egui.window.height() as f32);
+            let (min, max) = crate::display_cutout(size);
+            let (min, max) = (min / egui.scale_factor(), max / egui.scale_factor());
+            egui::Rect::from_min_max(egui::pos2(min.x, min.y), egui::pos2(max.x, max.y))
+        };
+        #[cfg(feature = "accesskit")]
+        if input.accessibility.is_enabled() {
+            egui.ctx.enable_accesskit();
+            input
+                .accessibility
+                .take_events(|event| input.raw.events.push(event));
+        }
+
+        for event in &input.raw.events {
+            if let Some(text) = event_text(event) {
+                egui.load_fonts_for(text);
+            }
+        }
+
+        // Nothing changed on screen: skip the frame
+        if !app.continuous_rendering()
+            && input.raw.events.is_empty()
+            && !egui.needs_repaint()
+            && egui.ctx.screen_rect() == content_rect
+        {
+            return;
+        }
 
         let (output, view) = match egui.gpu.get_output() {
             Ok(v) => v,
+            Err(wgpu::SurfaceError::Timeout) => {
+                log::warn!("Timed out waiting for the next surface texture, retrying");
+                egui.ctx.request_repaint();
+                return;
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                log::error!(
+                    "Out of GPU memory, freeing {} bytes of textures",
+                    egui.textures.bytes()
+                );
+                egui.textures.clear();
+                egui.ctx.request_repaint();
+                return;
+            }
             Err(err) => {
                 log::error!("GPU surface error: {err:?}");
                 return;
             }
         };
+        // Reconfiguring an outdated surface can also change its size
+        if let Some(size) = egui.gpu.take_resized() {
+            app.on_resized(egui, size);
+        }
         let mut encoder = egui.gpu.create_command_encoder();
 
         // --- egui ---
-        {
+        let callback_commands = {
             // --- create scene ---
-            // let screen_rect = {
-            //     let size = egui::vec2(egui.window.width() as f32, egui.window.height() as f32);
-            //     egui::Rect::from_min_size(egui::pos2(0.0, 0.0), size)
-            // };
-            let content_rect = {
-                let size = vec2(egui.window.width() as f32, egui.window.height() as f32);
-                let (min, max) = crate::display_cutout(size);
-                let (min, max) = (min / crate::scale_factor(), max / crate::scale_factor());
-                egui::Rect::from_min_max(egui::pos2(min.x, min.y), egui::pos2(max.x, max.y))
-            };
-            let mut input: egui::RawInput = input.take(content_rect);
-            let viewport = input
+            let mut raw_input: egui::RawInput = input.take(content_rect);
+            let viewport = raw_input
                 .viewports
                 .get_mut(&egui::viewport::ViewportId::ROOT)
                 .unwrap();
-            viewport.native_pixels_per_point = Some(crate::scale_factor());
+            viewport.native_pixels_per_point = Some(egui.scale_factor());
             viewport.inner_rect = Some(content_rect);
-            input.screen_rect = Some(content_rect);
+            raw_input.screen_rect = Some(content_rect);
             let ctx = egui.ctx.clone();
-            let egui_output = ctx.run(input, |ctx| {
+            *egui.repaint_at.lock().unwrap() = None;
+            std::mem::swap(&mut egui.gamepads, &mut input.translater.gamepads);
+            let egui_output = ctx.run(raw_input, |ctx| {
                 app.draw_frame(egui, ctx, stats);
+                input.show_paste_button(ctx);
             });
+            std::mem::swap(&mut egui.gamepads, &mut input.translater.gamepads);
+            if let Some(viewport) = egui_output.viewport_output.get(&egui::ViewportId::ROOT) {
+                schedule_repaint(&egui.repaint_at, viewport.repaint_delay);
+            }
+            input.handle_platform_output(&ctx, &egui_output.platform_output);
+            #[cfg(feature = "accesskit")]
+            if let Some(update) = &egui_output.platform_output.accesskit_update {
+                input
+                    .accessibility
+                    .update(update, egui_output.pixels_per_point);
+            }
+            let drawn_under_ui = app.render_under_ui(egui, &mut encoder, &view);
             let egui_prims = egui
                 .ctx
                 .tessellate(egui_output.shapes, egui_output.pixels_per_point);
@@ -207,7 +518,8 @@ impl<A: EguiApp> AppState for EguiAppState<A> {
                 egui.renderer
                     .update_texture(&egui.gpu.device, &egui.gpu.queue, id, &image);
             }
-            egui.renderer.update_buffers(
+            // Command buffers recorded by the `prepare` of paint callbacks
+            let callback_commands = egui.renderer.update_buffers(
                 &egui.gpu.device,
                 &egui.gpu.queue,
                 &mut encoder,
@@ -223,7 +535,10 @@ impl<A: EguiApp> AppState for EguiAppState<A> {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
+                        load: match drawn_under_ui {
+                            true => wgpu::LoadOp::Load,
+                            false => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        },
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -235,17 +550,40 @@ impl<A: EguiApp> AppState for EguiAppState<A> {
             for id in egui_output.textures_delta.free {
                 egui.renderer.free_texture(&id);
             }
+            callback_commands
         };
 
         // --- submit passes ---
-        egui.gpu.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        egui.gpu
+            .queue
+            .submit(callback_commands.into_iter().chain([encoder.finish()]));
+        egui.gpu.present(output);
+    }
+
+    fn frame_timeout(&mut self, frame_time: Duration) -> Option<Duration> {
+        let EguiAppState(input, egui, app) = self;
+        if app.continuous_rendering()
+            || !input.raw.events.is_empty()
+            || input.translater.long_press_pending()
+        {
+            return Some(frame_time);
+        }
+        // Sleeps until the next input or repaint request while egui has nothing to animate
+        egui.as_ref()
+            .filter(|egui| !egui.is_suspended())?
+            .repaint_delay()
+            .map(|delay| delay.max(frame_time))
     }
 
     fn on_picture_taken(&mut self, pic: Picture) {
         let EguiAppState(_input, egui, app) = self;
         app.on_picture_taken(egui, pic)
     }
+
+    fn on_clipboard_changed(&mut self) {
+        let EguiAppState(_input, egui, app) = self;
+        app.on_clipboard_changed(egui)
+    }
 }
 
 impl From<PtrButton> for egui::PointerButton {
@@ -259,17 +597,242 @@ impl From<PtrButton> for egui::PointerButton {
         }
     }
 }
+impl From<Modifiers> for egui::Modifiers {
+    fn from(modifiers: Modifiers) -> Self {
+        Self {
+            alt: modifiers.alt,
+            ctrl: modifiers.ctrl,
+            shift: modifiers.shift,
+            mac_cmd: false,
+            command: modifiers.ctrl,
+        }
+    }
+}
+
+/// Maps an Android `Keycode` to the `egui::Key` with the same meaning, if there is one.
+pub fn egui_key(key: Keycode) -> Option<egui::Key> {
+    use egui::Key;
+    Some(match key {
+        Keycode::DpadUp => Key::ArrowUp,
+        Keycode::DpadDown => Key::ArrowDown,
+        Keycode::DpadLeft => Key::ArrowLeft,
+        Keycode::DpadRight => Key::ArrowRight,
+        Keycode::Escape => Key::Escape,
+        Keycode::Tab => Key::Tab,
+        Keycode::Del => Key::Backspace,
+        Keycode::ForwardDel => Key::Delete,
+        Keycode::Enter | Keycode::NumpadEnter | Keycode::DpadCenter => Key::Enter,
+        Keycode::Space => Key::Space,
+        Keycode::Insert => Key::Insert,
+        Keycode::MoveHome => Key::Home,
+        Keycode::MoveEnd => Key::End,
+        Keycode::PageUp => Key::PageUp,
+        Keycode::PageDown => Key::PageDown,
+        Keycode::Copy => Key::Copy,
+        Keycode::Cut => Key::Cut,
+        Keycode::Paste => Key::Paste,
+        Keycode::Comma | Keycode::NumpadComma => Key::Comma,
+        Keycode::Period | Keycode::NumpadDot => Key::Period,
+        Keycode::Backslash => Key::Backslash,
+        Keycode::Slash | Keycode::NumpadDivide => Key::Slash,
+        Keycode::LeftBracket => Key::OpenBracket,
+        Keycode::RightBracket => Key::CloseBracket,
+        Keycode::Grave => Key::Backtick,
+        Keycode::Minus | Keycode::NumpadSubtract => Key::Minus,
+        Keycode::Plus | Keycode::NumpadAdd => Key::Plus,
+        Keycode::Equals | Keycode::NumpadEquals => Key::Equals,
+        Keycode::Semicolon => Key::Semicolon,
+        #[cfg(feature = "egui_28")]
+        Keycode::Apostrophe => Key::Quote,
+        Keycode::Keycode0 | Keycode::Numpad0 => Key::Num0,
+        Keycode::Keycode1 | Keycode::Numpad1 => Key::Num1,
+        Keycode::Keycode2 | Keycode::Numpad2 => Key::Num2,
+        Keycode::Keycode3 | Keycode::Numpad3 => Key::Num3,
+        Keycode::Keycode4 | Keycode::Numpad4 => Key::Num4,
+        Keycode::Keycode5 | Keycode::Numpad5 => Key::Num5,
+        Keycode::Keycode6 | Keycode::Numpad6 => Key::Num6,
+        Keycode::Keycode7 | Keycode::Numpad7 => Key::Num7,
+        Keycode::Keycode8 | Keycode::Numpad8 => Key::Num8,
+        Keycode::Keycode9 | Keycode::Numpad9 => Key::Num9,
+        Keycode::A => Key::A,
+        Keycode::B => Key::B,
+        Keycode::C => Key::C,
+        Keycode::D => Key::D,
+        Keycode::E => Key::E,
+        Keycode::F => Key::F,
+        Keycode::G => Key::G,
+        Keycode::H => Key::H,
+        Keycode::I => Key::I,
+        Keycode::J => Key::J,
+        Keycode::K => Key::K,
+        Keycode::L => Key::L,
+        Keycode::M => Key::M,
+        Keycode::N => Key::N,
+        Keycode::O => Key::O,
+        Keycode::P => Key::P,
+        Keycode::Q => Key::Q,
+        Keycode::R => Key::R,
+        Keycode::S => Key::S,
+        Keycode::T => Key::T,
+        Keycode::U => Key::U,
+        Keycode::V => Key::V,
+        Keycode::W => Key::W,
+        Keycode::X => Key::X,
+        Keycode::Y => Key::Y,
+        Keycode::Z => Key::Z,
+        Keycode::F1 => Key::F1,
+        Keycode::F2 => Key::F2,
+        Keycode::F3 => Key::F3,
+        Keycode::F4 => Key::F4,
+        Keycode::F5 => Key::F5,
+        Keycode::F6 => Key::F6,
+        Keycode::F7 => Key::F7,
+        Keycode::F8 => Key::F8,
+        Keycode::F9 => Key::F9,
+        Keycode::F10 => Key::F10,
+        Keycode::F11 => Key::F11,
+        Keycode::F12 => Key::F12,
+        _ => return None,
+    })
+}
+
+/// Selects the chars in `range` of the text field `id`.
+fn set_text_selection(ctx: &egui::Context, id: egui::Id, range: Range<usize>) {
+    use egui::text::{CCursor, CCursorRange};
+    let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+    let (start, end) = (CCursor::new(range.start), CCursor::new(range.end));
+    state
+        .cursor
+        .set_char_range(Some(CCursorRange::two(start, end)));
+    egui::TextEdit::store_state(ctx, id, state);
+}
+
+/// The `egui::Key` a gamepad `button` navigates the UI with, if any.
+fn gamepad_key(button: GamepadButton) -> Option<egui::Key> {
+    use egui::Key;
+    Some(match button {
+        GamepadButton::DpadUp => Key::ArrowUp,
+        GamepadButton::DpadDown => Key::ArrowDown,
+        GamepadButton::DpadLeft => Key::ArrowLeft,
+        GamepadButton::DpadRight => Key::ArrowRight,
+        GamepadButton::A => Key::Enter,
+        GamepadButton::B => Key::Escape,
+        _ => return None,
+    })
+}
+
+/// The clipboard event egui expects for a pressed `key` (eg: `Event::Copy` for Ctrl+C).
+/// Pasting reads the clipboard, so it's done by `insert_pasted_text` instead.
+fn clipboard_event(key: egui::Key, modifiers: Modifiers) -> Option<egui::Event> {
+    use egui::Key;
+    match key {
+        Key::Copy => Some(egui::Event::Copy),
+        Key::Cut => Some(egui::Event::Cut),
+        Key::C if modifiers.ctrl => Some(egui::Event::Copy),
+        Key::X if modifiers.ctrl => Some(egui::Event::Cut),
+        _ => None,
+    }
+}
+
+/// Follows every paste shortcut (the Paste key or Ctrl+V) in `events` with an `Event::Paste` of the clipboard's text.
+fn insert_pasted_text(events: &mut Vec<egui::Event>) {
+    use egui::Key;
+    let mut idx = 0;
+    while idx < events.len() {
+        idx += 1;
+        let egui::Event::Key {
+            key,
+            pressed: true,
+            modifiers,
+            ..
+        } = &events[idx - 1]
+        else {
+            continue;
+        };
+        if !(*key == Key::Paste || (*key == Key::V && modifiers.ctrl)) {
+            continue;
+        }
+        match crate::clipboard::get_text() {
+            Ok(Some(text)) => {
+                events.insert(idx, egui::Event::Paste(text));
+                idx += 1;
+            }
+            Ok(None) => {}
+            Err(err) => log::warn!("Failed to paste: {err}"),
+        }
+    }
+}
+
+/// The text typed or pasted by `event`.
+fn event_text(event: &egui::Event) -> Option<&str> {
+    match event {
+        egui::Event::Text(text) | egui::Event::Paste(text) => Some(text),
+        #[cfg(feature = "egui_28")]
+        egui::Event::Ime(egui::ImeEvent::Preedit(text) | egui::ImeEvent::Commit(text)) => {
+            Some(text)
+        }
+        #[cfg(feature = "egui_27")]
+        egui::Event::CompositionUpdate(text) | egui::Event::CompositionEnd(text) => Some(text),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "egui_28")]
+fn egui_ime_event(event: ImeEvent) -> egui::Event {
+    egui::Event::Ime(match event {
+        ImeEvent::Enabled => egui::ImeEvent::Enabled,
+        ImeEvent::Preedit(text) => egui::ImeEvent::Preedit(text),
+        ImeEvent::Commit(text) => egui::ImeEvent::Commit(text),
+        ImeEvent::Disabled => egui::ImeEvent::Disabled,
+    })
+}
+// egui 0.27 only has composition events
+#[cfg(feature = "egui_27")]
+fn egui_ime_event(event: ImeEvent) -> egui::Event {
+    match event {
+        ImeEvent::Enabled => egui::Event::CompositionStart,
+        ImeEvent::Preedit(text) => egui::Event::CompositionUpdate(text),
+        ImeEvent::Commit(text) => egui::Event::CompositionEnd(text),
+        ImeEvent::Disabled => egui::Event::CompositionEnd(String::new()),
+    }
+}
+
+impl From<TouchPhase> for egui::TouchPhase {
+    fn from(phase: TouchPhase) -> Self {
+        match phase {
+            TouchPhase::Start => egui::TouchPhase::Start,
+            TouchPhase::Move => egui::TouchPhase::Move,
+            TouchPhase::End => egui::TouchPhase::End,
+            TouchPhase::Cancel => egui::TouchPhase::Cancel,
+        }
+    }
+}
 impl From<TouchEvent> for Vec<egui::Event> {
     fn from(ptr: TouchEvent) -> Self {
         let first = match ptr {
-            TouchEvent::KeyBackspace { pressed } => egui::Event::Key {
-                key: egui::Key::Backspace,
-                physical_key: None,
+            TouchEvent::Key {
+                key,
                 pressed,
-                repeat: false,
-                modifiers: Default::default(),
-            },
+                repeat,
+                modifiers,
+            } => {
+                let Some(key) = egui_key(key) else {
+                    return vec![];
+                };
+                let event = egui::Event::Key {
+                    key,
+                    physical_key: None,
+                    pressed,
+                    repeat,
+                    modifiers: modifiers.into(),
+                };
+                return match pressed.then(|| clipboard_event(key, modifiers)).flatten() {
+                    Some(clipboard_event) => vec![event, clipboard_event],
+                    None => vec![event],
+                };
+            }
             TouchEvent::Text(text) => egui::Event::Text(text),
+            TouchEvent::Ime(event) => egui_ime_event(event),
             TouchEvent::PtrMoved(pos) => egui::Event::PointerMoved(egui::pos2(pos.x, pos.y)),
             TouchEvent::PtrPressed(button, pos) => egui::Event::PointerButton {
                 pos: egui::pos2(pos.x, pos.y),
@@ -295,15 +858,101 @@ impl From<TouchEvent> for Vec<egui::Event> {
                 delta: egui::vec2(delta.x, delta.y),
                 modifiers: Default::default(),
             },
+            TouchEvent::Gamepad(GamepadEvent::Button {
+                button, pressed, ..
+            }) => {
+                let Some(key) = gamepad_key(button) else {
+                    return vec![];
+                };
+                egui::Event::Key {
+                    key,
+                    physical_key: None,
+                    pressed,
+                    repeat: false,
+                    modifiers: Default::default(),
+                }
+            }
+            TouchEvent::Gesture(_) | TouchEvent::Gamepad(_) => return vec![],
+            TouchEvent::Touch {
+                id,
+                phase,
+                pos,
+                pressure,
+                ..
+            } => egui::Event::Touch {
+                device_id: egui::TouchDeviceId(0),
+                id: egui::TouchId(id as u64),
+                phase: phase.into(),
+                pos: egui::pos2(pos.x, pos.y),
+                force: Some(pressure.clamp(0.0, 1.0)),
+            },
+            TouchEvent::Pen {
+                id,
+                phase,
+                pos,
+                pressure,
+                ..
+            } => egui::Event::Touch {
+                device_id: egui::TouchDeviceId(1),
+                id: egui::TouchId(id as u64),
+                phase: phase.into(),
+                pos: egui::pos2(pos.x, pos.y),
+                force: Some(pressure.clamp(0.0, 1.0)),
+            },
         };
         vec![first]
     }
 }
 
+/// Maps an egui cursor icon to the closest `android.view.PointerIcon.TYPE_*` constant.
+fn pointer_icon_type(icon: egui::CursorIcon) -> i32 {
+    use egui::CursorIcon;
+    match icon {
+        CursorIcon::None => 0,
+        CursorIcon::ContextMenu => 1001,
+        CursorIcon::PointingHand => 1002,
+        CursorIcon::Help => 1003,
+        CursorIcon::Progress | CursorIcon::Wait => 1004,
+        CursorIcon::Cell => 1006,
+        CursorIcon::Crosshair => 1007,
+        CursorIcon::Text => 1008,
+        CursorIcon::VerticalText => 1009,
+        CursorIcon::Alias => 1010,
+        CursorIcon::Copy => 1011,
+        CursorIcon::NoDrop | CursorIcon::NotAllowed => 1012,
+        CursorIcon::Move | CursorIcon::AllScroll => 1013,
+        CursorIcon::ResizeHorizontal
+        | CursorIcon::ResizeEast
+        | CursorIcon::ResizeWest
+        | CursorIcon::ResizeColumn => 1014,
+        CursorIcon::ResizeVertical
+        | CursorIcon::ResizeNorth
+        | CursorIcon::ResizeSouth
+        | CursorIcon::ResizeRow => 1015,
+        CursorIcon::ResizeNeSw | CursorIcon::ResizeNorthEast | CursorIcon::ResizeSouthWest => 1016,
+        CursorIcon::ResizeNwSe | CursorIcon::ResizeNorthWest | CursorIcon::ResizeSouthEast => 1017,
+        CursorIcon::ZoomIn => 1018,
+        CursorIcon::ZoomOut => 1019,
+        CursorIcon::Grab => 1020,
+        CursorIcon::Grabbing => 1021,
+        // TYPE_ARROW
+        _ => 1000,
+    }
+}
+
 #[derive(Default)]
 pub struct EguiInput {
     pub raw: egui::RawInput,
     pub translater: TouchTranslater,
+    keyboard_visible: bool,
+    ime_cursor_rect: Option<[i32; 4]>,
+    // Where to show the "Paste" button, and the text field to paste into.
+    paste_button: Option<(egui::Pos2, egui::Id)>,
+    cursor_icon: egui::CursorIcon,
+    // The selection to set in the focused text field once the soft keyboard's edit is applied.
+    text_selection: Option<Range<usize>>,
+    #[cfg(feature = "accesskit")]
+    pub accessibility: crate::accessibility::AccessibilityBridge,
 }
 impl EguiInput {
     pub fn take_raw(&mut self) -> egui::RawInput {
@@ -316,7 +965,7 @@ impl EguiInput {
             .viewports
             .get_mut(&egui::viewport::ViewportId::ROOT)
             .unwrap();
-        viewport.native_pixels_per_point = Some(scale_factor());
+        viewport.native_pixels_per_point = Some(self.translater.scale_factor());
         viewport.inner_rect = Some(content_rect);
         input.screen_rect = Some(content_rect);
         input
@@ -326,4 +975,162 @@ impl EguiInput {
         self.translater
             .update(|e| self.raw.events.extend(Vec::<_>::from(e)));
     }
+
+    /// Acts on what egui asks of the platform: copied text, opened URLs, the mouse pointer icon and the soft keyboard.
+    pub fn handle_platform_output(&mut self, ctx: &egui::Context, output: &egui::PlatformOutput) {
+        if !output.copied_text.is_empty() {
+            if let Err(err) = crate::set_clipboard_content(&output.copied_text) {
+                log::warn!("{err}");
+            }
+        }
+        if let Some(open_url) = &output.open_url {
+            if let Err(err) = crate::open_url(&open_url.url) {
+                log::warn!("{err}");
+            }
+        }
+        if output.cursor_icon != self.cursor_icon {
+            self.cursor_icon = output.cursor_icon;
+            if let Err(err) = crate::set_pointer_icon(pointer_icon_type(output.cursor_icon)) {
+                log::warn!("{err}");
+            }
+        }
+        if let Some(ime) = &output.ime {
+            let rect = ime.cursor_rect * ctx.pixels_per_point();
+            let rect = [rect.min.x, rect.min.y, rect.max.x, rect.max.y].map(|v| v.round() as i32);
+            if self.ime_cursor_rect != Some(rect) {
+                self.ime_cursor_rect = Some(rect);
+                if let Err(err) = crate::set_ime_cursor_rect(rect) {
+                    log::warn!("{err}");
+                }
+            }
+        } else {
+            self.ime_cursor_rect = None;
+        }
+        self.sync_text_input(ctx, output);
+    }
+
+    /// Shows a "Paste" button after a long press (or right click) on the focused text field,
+    /// which pastes the clipboard's text into it.
+    /// Must be called after the app's UI is drawn, in the same frame.
+    pub fn show_paste_button(&mut self, ctx: &egui::Context) {
+        let text_field = ctx.output(|o| o.ime.is_some());
+        let focused = ctx.memory(|mem| mem.focused());
+        let long_pressed = ctx.input(|i| i.pointer.button_clicked(egui::PointerButton::Secondary));
+        let mut opened = false;
+        if let (true, true, Some(id), Some(pos)) = (
+            long_pressed,
+            text_field,
+            focused,
+            ctx.input(|i| i.pointer.interact_pos()),
+        ) {
+            self.paste_button = Some((pos, id));
+            opened = true;
+        }
+        let Some((pos, id)) = self.paste_button else {
+            return;
+        };
+
+        let mut paste = false;
+        let area = egui::Area::new(egui::Id::new("jano::paste_button"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    paste = ui.button("Paste").clicked();
+                });
+            });
+        if paste {
+            self.paste_button = None;
+            match crate::clipboard::get_text() {
+                Ok(Some(text)) => {
+                    // Clicking the button took the focus away from the text field
+                    ctx.memory_mut(|mem| mem.request_focus(id));
+                    self.raw.events.push(egui::Event::Paste(text));
+                }
+                Ok(None) => {}
+                Err(err) => log::warn!("{err}"),
+            }
+        } else if !opened && area.response.clicked_elsewhere() {
+            self.paste_button = None;
+        }
+    }
+
+    /// Applies an edit of the soft keyboard to the focused text field:
+    /// selects the replaced chars, then sends the events replacing them.
+    pub fn apply_text_edit(&mut self, ctx: &egui::Context, edit: TextFieldEdit) {
+        let Some(id) = ctx.memory(|mem| mem.focused()) else {
+            return;
+        };
+        set_text_selection(ctx, id, edit.replace.clone());
+        if !edit.text.is_empty() {
+            self.raw.events.push(egui::Event::Text(edit.text));
+        } else if edit.compose.is_none() && !edit.replace.is_empty() {
+            // Deletes the selected chars
+            for pressed in [true, false] {
+                self.raw.events.push(egui::Event::Key {
+                    key: egui::Key::Backspace,
+                    physical_key: None,
+                    pressed,
+                    repeat: false,
+                    modifiers: Default::default(),
+                });
+            }
+        }
+        if let Some(compose) = edit.compose {
+            self.raw.events.push(egui_ime_event(ImeEvent::Enabled));
+            self.raw
+                .events
+                .push(egui_ime_event(ImeEvent::Preedit(compose)));
+        } else if edit.ends_composition {
+            self.raw.events.push(egui_ime_event(ImeEvent::Disabled));
+        }
+        self.text_selection = edit.selection;
+        ctx.request_repaint();
+    }
+
+    /// Shows the soft keyboard while a text field is focused or touched, and hides it otherwise.
+    /// Sends the contents of the focused text field to the keyboard.
+    pub fn sync_text_input(&mut self, ctx: &egui::Context, output: &egui::PlatformOutput) {
+        let wants_keyboard = output.ime.is_some() || output.mutable_text_under_cursor;
+        if wants_keyboard != self.keyboard_visible {
+            self.keyboard_visible = wants_keyboard;
+            if let Err(err) = crate::set_keyboard_visibility(wants_keyboard) {
+                log::warn!("{err}");
+            }
+            if !wants_keyboard {
+                self.translater.text_input.reset();
+            }
+        }
+        let Some(id) = ctx.memory(|mem| mem.focused()) else {
+            return;
+        };
+        if let Some(selection) = self.text_selection.take() {
+            set_text_selection(ctx, id, selection);
+            ctx.request_repaint();
+        }
+        // egui only reports the contents of a text field when it changes, gains focus or the selection changes.
+        let text = output
+            .events
+            .iter()
+            .rev()
+            .map(|event| event.widget_info())
+            .find(|info| info.typ == egui::WidgetType::TextEdit)
+            .and_then(|info| info.current_text_value.clone());
+        let Some(text) = text else {
+            return;
+        };
+        let selection = egui::TextEdit::load_state(ctx, id)
+            .and_then(|state| state.cursor.char_range())
+            .map(|range| {
+                let [min, max] = range.sorted();
+                min.index..max.index
+            })
+            .unwrap_or_else(|| {
+                let len = text.chars().count();
+                len..len
+            });
+        if let Some(state) = self.translater.text_input.sync_from_app(&text, selection) {
+            android().set_text_input_state(state);
+        }
+    }
 }
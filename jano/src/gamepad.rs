@@ -0,0 +1,540 @@
+#[cfg(target_os = "android")]
+use android_activity::{
+    input::{Axis, InputEvent, KeyAction, Keycode, MotionEvent, Source},
+    InputStatus,
+};
+use glam::{vec2, Vec2};
+
+use std::collections::{btree_map::Entry, BTreeMap};
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum GamepadButton {
+    A = 0,
+    B = 1,
+    X = 2,
+    Y = 3,
+    L1 = 4,
+    R1 = 5,
+    L2 = 6,
+    R2 = 7,
+    ThumbL = 8,
+    ThumbR = 9,
+    Start = 10,
+    Select = 11,
+    Mode = 12,
+    DpadUp = 13,
+    DpadDown = 14,
+    DpadLeft = 15,
+    DpadRight = 16,
+}
+impl GamepadButton {
+    const COUNT: usize = 17;
+
+    #[cfg(target_os = "android")]
+    pub fn from_keycode(key: Keycode) -> Option<Self> {
+        Some(match key {
+            Keycode::ButtonA => Self::A,
+            Keycode::ButtonB => Self::B,
+            Keycode::ButtonX => Self::X,
+            Keycode::ButtonY => Self::Y,
+            Keycode::ButtonL1 => Self::L1,
+            Keycode::ButtonR1 => Self::R1,
+            Keycode::ButtonL2 => Self::L2,
+            Keycode::ButtonR2 => Self::R2,
+            Keycode::ButtonThumbl => Self::ThumbL,
+            Keycode::ButtonThumbr => Self::ThumbR,
+            Keycode::ButtonStart => Self::Start,
+            Keycode::ButtonSelect => Self::Select,
+            Keycode::ButtonMode => Self::Mode,
+            Keycode::DpadUp => Self::DpadUp,
+            Keycode::DpadDown => Self::DpadDown,
+            Keycode::DpadLeft => Self::DpadLeft,
+            Keycode::DpadRight => Self::DpadRight,
+            _ => return None,
+        })
+    }
+}
+
+/// An analog axis of a gamepad.
+/// Sticks range from -1 to 1 (positive is right/down) and triggers from 0 to 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum GamepadAxis {
+    LeftX = 0,
+    LeftY = 1,
+    RightX = 2,
+    RightY = 3,
+    LeftTrigger = 4,
+    RightTrigger = 5,
+}
+impl GamepadAxis {
+    const COUNT: usize = 6;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GamepadEvent {
+    Connected {
+        id: i32,
+        name: String,
+    },
+    Disconnected(i32),
+    Button {
+        id: i32,
+        button: GamepadButton,
+        pressed: bool,
+    },
+}
+
+/// The gamepads reported by MainActivity or seen through input events, shared by every `Gamepads`.
+/// `None` until the first `Gamepads::update`, so that nothing is recorded for apps that don't use gamepads.
+static CONNECTED: Mutex<Option<ConnectedPads>> = Mutex::new(None);
+
+#[derive(Default)]
+struct ConnectedPads {
+    // Incremented on every change, so that `Gamepads::update` only compares the pads when something changed.
+    generation: u64,
+    names: BTreeMap<i32, String>,
+}
+
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+fn device_connected(id: i32, name: String) {
+    if let Some(connected) = CONNECTED.lock().unwrap().as_mut() {
+        connected.names.insert(id, name);
+        connected.generation += 1;
+    }
+}
+
+/// Lists a gamepad whose input arrived before MainActivity reported it, so that it's disconnected like the others.
+fn device_seen(id: i32) {
+    if let Some(connected) = CONNECTED.lock().unwrap().as_mut() {
+        if let Entry::Vacant(entry) = connected.names.entry(id) {
+            entry.insert(String::new());
+            connected.generation += 1;
+        }
+    }
+}
+
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+fn device_removed(id: i32) {
+    if let Some(connected) = CONNECTED.lock().unwrap().as_mut() {
+        if connected.names.remove(&id).is_some() {
+            connected.generation += 1;
+        }
+    }
+}
+
+/// Starts recording the gamepads reported by MainActivity, and asks it to report the ones already connected.
+fn enable_device_reports() {
+    {
+        let mut connected = CONNECTED.lock().unwrap();
+        if connected.is_some() {
+            return;
+        }
+        *connected = Some(ConnectedPads::default());
+    }
+    // MainActivity calls `onGamepadConnected` before returning, so the lock must be released
+    #[cfg(target_os = "android")]
+    if let Err(err) = crate::call_activity("reportGamepads", "()V", &[], |_, _| Ok(())) {
+        log::warn!("Failed to report the connected gamepads: {err}");
+    }
+}
+
+#[cfg(target_os = "android")]
+#[allow(dead_code)]
+#[allow(non_snake_case)]
+#[no_mangle]
+/// Called by the JVM when a gamepad or joystick is connected, and for the ones already connected when gamepads are first updated.
+pub extern "C" fn Java_nodomain_jano_MainActivity_onGamepadConnected(
+    mut env: jni::JNIEnv,
+    _class: jni::objects::JObject,
+    id: jni::sys::jint,
+    name: jni::objects::JString,
+) {
+    let name = match env.get_string(&name) {
+        Ok(name) => name.to_string_lossy().to_string(),
+        Err(err) => {
+            log::warn!("Failed to read name of gamepad {id}: {err:?}");
+            String::new()
+        }
+    };
+    log::info!("Gamepad {id} connected: {name}");
+    device_connected(id, name);
+    crate::wake_main_loop();
+}
+
+#[cfg(target_os = "android")]
+#[allow(dead_code)]
+#[allow(non_snake_case)]
+#[no_mangle]
+/// Called by the JVM when any input device is disconnected.
+pub extern "C" fn Java_nodomain_jano_MainActivity_onInputDeviceRemoved(
+    _env: jni::JNIEnv,
+    _class: jni::objects::JObject,
+    id: jni::sys::jint,
+) {
+    device_removed(id);
+    crate::wake_main_loop();
+}
+
+/// Scales `v` so that it's zero inside the dead zone and reaches 1 at the edge.
+fn apply_dead_zone(v: f32, dead_zone: f32) -> f32 {
+    if v.abs() <= dead_zone {
+        return 0.0;
+    }
+    (v.abs() - dead_zone) / (1.0 - dead_zone) * v.signum()
+}
+
+/// Same as `apply_dead_zone`, but using the distance of the stick from the center.
+fn apply_radial_dead_zone(v: Vec2, dead_zone: f32) -> Vec2 {
+    let len = v.length();
+    if len <= dead_zone {
+        return Vec2::ZERO;
+    }
+    v / len * ((len - dead_zone) / (1.0 - dead_zone)).min(1.0)
+}
+
+#[derive(Clone, Debug)]
+pub struct Gamepad {
+    pub id: i32,
+    pub name: String,
+    buttons: [bool; GamepadButton::COUNT],
+    // The frame in which each button was last pressed or released.
+    changed: [u64; GamepadButton::COUNT],
+    axes: [f32; GamepadAxis::COUNT],
+    #[cfg(target_os = "android")]
+    hat: Vec2,
+    frame: u64,
+    dead_zone: f32,
+}
+impl Gamepad {
+    fn new(id: i32, name: String) -> Self {
+        Self {
+            id,
+            name,
+            buttons: [false; GamepadButton::COUNT],
+            changed: [0; GamepadButton::COUNT],
+            axes: [0.0; GamepadAxis::COUNT],
+            #[cfg(target_os = "android")]
+            hat: Vec2::ZERO,
+            frame: 0,
+            dead_zone: 0.0,
+        }
+    }
+
+    pub fn pressed(&self, button: GamepadButton) -> bool {
+        self.buttons[button as usize]
+    }
+
+    /// Whether `button` went down this frame.
+    pub fn just_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed(button) && self.changed[button as usize] == self.frame
+    }
+
+    /// Whether `button` went up this frame.
+    pub fn just_released(&self, button: GamepadButton) -> bool {
+        !self.pressed(button) && self.changed[button as usize] == self.frame
+    }
+
+    /// The value of `axis` with the dead zone applied.
+    /// Use `left_stick` and `right_stick` for a circular dead zone.
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        apply_dead_zone(self.raw_axis(axis), self.dead_zone)
+    }
+
+    pub fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    pub fn left_stick(&self) -> Vec2 {
+        let v = vec2(
+            self.raw_axis(GamepadAxis::LeftX),
+            self.raw_axis(GamepadAxis::LeftY),
+        );
+        apply_radial_dead_zone(v, self.dead_zone)
+    }
+
+    pub fn right_stick(&self) -> Vec2 {
+        let v = vec2(
+            self.raw_axis(GamepadAxis::RightX),
+            self.raw_axis(GamepadAxis::RightY),
+        );
+        apply_radial_dead_zone(v, self.dead_zone)
+    }
+
+    #[cfg(target_os = "android")]
+    fn set_button(
+        &mut self,
+        button: GamepadButton,
+        pressed: bool,
+        mut out: impl FnMut(GamepadEvent),
+    ) {
+        let idx = button as usize;
+        if self.buttons[idx] == pressed {
+            return;
+        }
+        self.buttons[idx] = pressed;
+        self.changed[idx] = self.frame;
+        out(GamepadEvent::Button {
+            id: self.id,
+            button,
+            pressed,
+        });
+    }
+}
+
+/// Tracks the state of every connected gamepad.
+/// Call `update` once per frame, before handling the frame's input events.
+/// Every instance sees the gamepads connected and disconnected, independently of the others.
+#[derive(Clone, Debug)]
+pub struct Gamepads {
+    pads: BTreeMap<i32, Gamepad>,
+    frame: u64,
+    // The `ConnectedPads::generation` the pads were last compared with.
+    generation: u64,
+    /// Stick and trigger values below this are reported as zero.
+    pub dead_zone: f32,
+}
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self {
+            pads: BTreeMap::new(),
+            frame: 1,
+            generation: 0,
+            dead_zone: 0.15,
+        }
+    }
+}
+impl Gamepads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, id: i32) -> Option<&Gamepad> {
+        self.pads.get(&id)
+    }
+
+    /// The connected gamepads, ordered by device id.
+    pub fn iter(&self) -> impl Iterator<Item = &Gamepad> {
+        self.pads.values()
+    }
+
+    /// The gamepad with the lowest device id, for games with a single player.
+    pub fn first(&self) -> Option<&Gamepad> {
+        self.pads.values().next()
+    }
+
+    /// Starts a new frame and applies the gamepads connected or disconnected since the last update.
+    pub fn update(&mut self, mut out: impl FnMut(GamepadEvent)) {
+        self.frame += 1;
+        for pad in self.pads.values_mut() {
+            pad.frame = self.frame;
+            pad.dead_zone = self.dead_zone;
+        }
+
+        enable_device_reports();
+        let connected = CONNECTED.lock().unwrap();
+        let Some(connected) = connected.as_ref() else {
+            return;
+        };
+        if connected.generation == self.generation {
+            return;
+        }
+        self.generation = connected.generation;
+
+        for (&id, name) in &connected.names {
+            if let Some(pad) = self.pads.get_mut(&id) {
+                // Pads seen through input events are named once MainActivity reports them
+                pad.name.clone_from(name);
+                continue;
+            }
+            self.connect(id, name.clone());
+            out(GamepadEvent::Connected {
+                id,
+                name: name.clone(),
+            });
+        }
+        self.pads.retain(|&id, _| {
+            if connected.names.contains_key(&id) {
+                return true;
+            }
+            log::info!("Gamepad {id} disconnected");
+            out(GamepadEvent::Disconnected(id));
+            false
+        });
+    }
+
+    fn connect(&mut self, id: i32, name: String) -> &mut Gamepad {
+        let pad = self
+            .pads
+            .entry(id)
+            .or_insert_with(|| Gamepad::new(id, name));
+        pad.frame = self.frame;
+        pad.dead_zone = self.dead_zone;
+        pad
+    }
+
+    /// Returns the gamepad for `id`, registering it if its input arrived before MainActivity reported it.
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    fn pad(&mut self, id: i32, out: &mut impl FnMut(GamepadEvent)) -> &mut Gamepad {
+        if !self.pads.contains_key(&id) {
+            device_seen(id);
+            out(GamepadEvent::Connected {
+                id,
+                name: String::new(),
+            });
+        }
+        self.connect(id, String::new())
+    }
+
+    /// Updates the gamepad state from `event`.
+    /// Returns `InputStatus::Unhandled` if the event doesn't come from a gamepad or joystick.
+    #[cfg(target_os = "android")]
+    pub fn handle_input_event(
+        &mut self,
+        event: &InputEvent,
+        mut out: impl FnMut(GamepadEvent),
+    ) -> InputStatus {
+        match event {
+            InputEvent::KeyEvent(key_event) => {
+                let Some(button) = GamepadButton::from_keycode(key_event.key_code()) else {
+                    return InputStatus::Unhandled;
+                };
+                if !has_source(key_event.source(), Source::Gamepad) {
+                    return InputStatus::Unhandled;
+                }
+                let pressed = match key_event.action() {
+                    KeyAction::Down => true,
+                    KeyAction::Up => false,
+                    _ => return InputStatus::Unhandled,
+                };
+                self.pad(key_event.device_id(), &mut out)
+                    .set_button(button, pressed, out);
+            }
+            InputEvent::MotionEvent(motion_event) => {
+                if !has_source(motion_event.source(), Source::Joystick) {
+                    return InputStatus::Unhandled;
+                }
+                let pad = self.pad(motion_event.device_id(), &mut out);
+                update_axes(pad, motion_event, out);
+            }
+            _ => return InputStatus::Unhandled,
+        }
+        InputStatus::Handled
+    }
+}
+
+#[cfg(target_os = "android")]
+fn has_source(source: Source, class: Source) -> bool {
+    let source: u32 = source.into();
+    let class: u32 = class.into();
+    source & class == class
+}
+
+#[cfg(target_os = "android")]
+fn update_axes(pad: &mut Gamepad, motion_event: &MotionEvent, mut out: impl FnMut(GamepadEvent)) {
+    let ptr = motion_event.pointer_at_index(0);
+    let axis = |axis| ptr.axis_value(axis);
+    pad.axes = [
+        axis(Axis::X),
+        axis(Axis::Y),
+        axis(Axis::Z),
+        axis(Axis::Rz),
+        // Controllers report triggers as either LTRIGGER/RTRIGGER or BRAKE/GAS
+        axis(Axis::Ltrigger).max(axis(Axis::Brake)),
+        axis(Axis::Rtrigger).max(axis(Axis::Gas)),
+    ];
+
+    // Many controllers report the d-pad as a hat instead of key presses
+    let hat = vec2(axis(Axis::HatX), axis(Axis::HatY));
+    if hat == pad.hat {
+        return;
+    }
+    pad.hat = hat;
+    let (hat_x, hat_y) = (hat.x, hat.y);
+    pad.set_button(GamepadButton::DpadLeft, hat_x < -0.5, &mut out);
+    pad.set_button(GamepadButton::DpadRight, hat_x > 0.5, &mut out);
+    pad.set_button(GamepadButton::DpadUp, hat_y < -0.5, &mut out);
+    pad.set_button(GamepadButton::DpadDown, hat_y > 0.5, &mut out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(gamepads: &mut Gamepads) -> Vec<GamepadEvent> {
+        let mut events = vec![];
+        gamepads.update(|e| events.push(e));
+        events
+    }
+
+    // The only test touching `CONNECTED`, so that tests running in parallel don't see each other's devices
+    #[test]
+    fn device_changes_are_recorded_once_enabled_and_seen_by_every_instance() {
+        device_connected(1, "Too early".into());
+        assert!(CONNECTED.lock().unwrap().is_none());
+
+        let mut a = Gamepads::new();
+        let mut b = Gamepads::new();
+        assert_eq!(update(&mut a), vec![]);
+        assert_eq!(update(&mut b), vec![]);
+
+        device_connected(7, "Pad".into());
+        let connected = GamepadEvent::Connected {
+            id: 7,
+            name: "Pad".into(),
+        };
+        assert_eq!(update(&mut a), vec![connected.clone()]);
+        assert_eq!(update(&mut b), vec![connected]);
+        assert_eq!(a.first().map(|pad| pad.name.as_str()), Some("Pad"));
+        assert!(a.get(1).is_none());
+        assert_eq!(update(&mut a), vec![]);
+
+        // A keyboard or mouse being removed doesn't change anything
+        device_removed(99);
+        device_removed(7);
+        assert_eq!(update(&mut a), vec![GamepadEvent::Disconnected(7)]);
+        assert_eq!(update(&mut b), vec![GamepadEvent::Disconnected(7)]);
+        assert_eq!(CONNECTED.lock().unwrap().as_ref().unwrap().names.len(), 0);
+
+        // Gamepads created later get the ones already connected
+        device_connected(8, "Late".into());
+        let mut c = Gamepads::new();
+        assert_eq!(
+            update(&mut c),
+            vec![GamepadEvent::Connected {
+                id: 8,
+                name: "Late".into()
+            }]
+        );
+        assert_eq!(a.iter().count(), 0);
+        assert_eq!(update(&mut a).len(), 1);
+        assert_eq!(update(&mut b).len(), 1);
+
+        // Pads whose input arrives before MainActivity reports them are disconnected like the others
+        let mut events = vec![];
+        a.pad(5, &mut |e| events.push(e));
+        let unnamed = GamepadEvent::Connected {
+            id: 5,
+            name: String::new(),
+        };
+        assert_eq!(events, vec![unnamed.clone()]);
+        assert_eq!(update(&mut a), vec![]);
+        assert_eq!(update(&mut b), vec![unnamed]);
+        device_connected(5, "Named".into());
+        assert_eq!(update(&mut a), vec![]);
+        assert_eq!(a.get(5).map(|pad| pad.name.as_str()), Some("Named"));
+        device_removed(5);
+        assert_eq!(update(&mut a), vec![GamepadEvent::Disconnected(5)]);
+        assert_eq!(update(&mut b), vec![GamepadEvent::Disconnected(5)]);
+    }
+
+    #[test]
+    fn dead_zone_scales_to_the_edge() {
+        assert_eq!(apply_dead_zone(0.1, 0.2), 0.0);
+        assert!((apply_dead_zone(-0.6, 0.2) + 0.5).abs() < 1e-6);
+        assert_eq!(apply_dead_zone(1.0, 0.2), 1.0);
+        assert_eq!(apply_radial_dead_zone(vec2(0.1, 0.1), 0.2), Vec2::ZERO);
+        let v = apply_radial_dead_zone(vec2(0.0, -0.6), 0.2);
+        assert!((v - vec2(0.0, -0.5)).length() < 1e-6);
+    }
+}
@@ -1,10 +1,15 @@
+#[cfg(feature = "canvas")]
+pub mod canvas;
+
 use super::wgpu;
+use crate::Picture;
 use glam::{uvec2, UVec2};
 
+#[cfg(target_os = "android")]
 pub fn create_wgpu_surface(
     instance: &wgpu::Instance,
     window: &crate::Window,
-) -> wgpu::Surface<'static> {
+) -> Result<wgpu::Surface<'static>, String> {
     let win_ptr = window.ptr().cast();
     unsafe {
         instance
@@ -12,65 +17,416 @@ pub fn create_wgpu_surface(
                 raw_display_handle: wgpu::rwh::AndroidDisplayHandle::new().into(),
                 raw_window_handle: wgpu::rwh::AndroidNdkWindowHandle::new(win_ptr).into(),
             })
-            .unwrap()
+            .map_err(|err| format!("Failed to create surface : {err}"))
+    }
+}
+
+/// How `Gpu::new` picks and configures the adapter, device and surface.
+#[derive(Clone, Debug)]
+pub struct GpuConfig {
+    /// The backends to try, in order, until one gives a usable device.
+    pub backends: Vec<wgpu::Backends>,
+    pub power_preference: wgpu::PowerPreference,
+    /// Features the device must support.
+    pub required_features: wgpu::Features,
+    /// Features enabled if the adapter supports them.
+    pub optional_features: wgpu::Features,
+    /// The limits the device must support, `None` for the best limits of the adapter.
+    pub limits: Option<wgpu::Limits>,
+    /// Falls back to `Fifo` (vsync, always supported) if the surface doesn't support it.
+    pub present_mode: wgpu::PresentMode,
+    /// `None` for the first mode the surface supports.
+    pub alpha_mode: Option<wgpu::CompositeAlphaMode>,
+    pub desired_maximum_frame_latency: u32,
+    /// Whether to pick an sRGB or a non-sRGB surface format, `None` for the surface's preferred format.
+    pub srgb: Option<bool>,
+    /// Keeps a copy of every presented frame, for `Gpu::capture_frame` (eg: for a screenshot).
+    /// Costs a copy of the frame every frame, and is ignored if the surface can't be copied from.
+    pub capture_frames: bool,
+}
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            backends: vec![wgpu::Backends::VULKAN, wgpu::Backends::GL],
+            power_preference: wgpu::PowerPreference::default(),
+            required_features: wgpu::Features::empty(),
+            optional_features: wgpu::Features::empty(),
+            limits: None,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: None,
+            desired_maximum_frame_latency: 2,
+            srgb: None,
+            capture_frames: false,
+        }
+    }
+}
+impl GpuConfig {
+    pub fn backends(mut self, backends: impl IntoIterator<Item = wgpu::Backends>) -> Self {
+        self.backends = backends.into_iter().collect();
+        self
+    }
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+    pub fn required_features(mut self, features: wgpu::Features) -> Self {
+        self.required_features = features;
+        self
+    }
+    pub fn optional_features(mut self, features: wgpu::Features) -> Self {
+        self.optional_features = features;
+        self
+    }
+    pub fn limits(mut self, limits: wgpu::Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+    pub fn alpha_mode(mut self, alpha_mode: wgpu::CompositeAlphaMode) -> Self {
+        self.alpha_mode = Some(alpha_mode);
+        self
+    }
+    pub fn desired_maximum_frame_latency(mut self, latency: u32) -> Self {
+        self.desired_maximum_frame_latency = latency;
+        self
+    }
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = Some(srgb);
+        self
+    }
+    pub fn capture_frames(mut self, capture_frames: bool) -> Self {
+        self.capture_frames = capture_frames;
+        self
+    }
+
+    /// The surface configuration for a `size` surface with `capabilities`.
+    fn surface_config(
+        &self,
+        capabilities: &wgpu::SurfaceCapabilities,
+        size: UVec2,
+    ) -> Result<wgpu::SurfaceConfiguration, String> {
+        let Some(preferred_format) = capabilities.formats.first() else {
+            Err("Surface is incompatible with the adapter")?
+        };
+        let format = match self.srgb {
+            Some(srgb) => capabilities
+                .formats
+                .iter()
+                .find(|format| format.is_srgb() == srgb)
+                .unwrap_or(preferred_format),
+            None => preferred_format,
+        };
+        let present_mode = match capabilities.present_modes.contains(&self.present_mode) {
+            true => self.present_mode,
+            false => {
+                log::warn!(
+                    "Present mode {:?} isn't supported, using Fifo",
+                    self.present_mode
+                );
+                wgpu::PresentMode::Fifo
+            }
+        };
+        let alpha_mode = match self.alpha_mode {
+            Some(mode) if capabilities.alpha_modes.contains(&mode) => mode,
+            Some(mode) => {
+                log::warn!("Alpha mode {mode:?} isn't supported");
+                capabilities.alpha_modes[0]
+            }
+            None => capabilities.alpha_modes[0],
+        };
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if self.capture_frames {
+            match capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+                true => usage |= wgpu::TextureUsages::COPY_SRC,
+                false => log::warn!("Surface can't be copied from, frames can't be captured"),
+            }
+        }
+        Ok(wgpu::SurfaceConfiguration {
+            usage,
+            format: *format,
+            width: size.x,
+            height: size.y,
+            present_mode,
+            alpha_mode,
+            view_formats: vec![],
+            desired_maximum_frame_latency: self.desired_maximum_frame_latency,
+        })
+    }
+
+    /// The configuration of a `size` offscreen texture.
+    fn offscreen_config(&self, size: UVec2) -> wgpu::SurfaceConfiguration {
+        let format = match self.srgb {
+            Some(false) => wgpu::TextureFormat::Rgba8Unorm,
+            _ => wgpu::TextureFormat::Rgba8UnormSrgb,
+        };
+        wgpu::SurfaceConfiguration {
+            // Also sampled, so it can be drawn in another pass
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            format,
+            width: size.x,
+            height: size.y,
+            present_mode: self.present_mode,
+            alpha_mode: self.alpha_mode.unwrap_or(wgpu::CompositeAlphaMode::Opaque),
+            view_formats: vec![],
+            desired_maximum_frame_latency: self.desired_maximum_frame_latency,
+        }
+    }
+}
+
+/// A frame being drawn, from `Gpu::get_output`, shown with `Gpu::present`.
+pub struct Frame {
+    // `None` when drawing to an offscreen texture
+    surface_texture: Option<wgpu::SurfaceTexture>,
+}
+impl Frame {
+    /// The surface texture drawn to, `None` for an offscreen `Gpu`.
+    pub fn surface_texture(&self) -> Option<&wgpu::SurfaceTexture> {
+        self.surface_texture.as_ref()
     }
 }
 
 pub struct Gpu {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    // Using ManuallyDrop to prevent the `drop` implementation of wgpu::Surface from running.
-    // In wgpu 0.20, wgpu::Surface::drop crashes on a null-pointer exception if ran after a `TerminateWindow` event is recieved.
-    pub surface: std::mem::ManuallyDrop<wgpu::Surface<'static>>,
+    /// `None` while suspended (between `TerminateWindow` and `InitWindow`), and for an offscreen `Gpu`.
+    pub surface: Option<wgpu::Surface<'static>>,
+    /// The configuration of the surface, or of the offscreen texture.
     pub surface_config: wgpu::SurfaceConfiguration,
+    /// The texture frames are drawn to instead of a surface, for a `Gpu` created with `new_offscreen`.
+    pub offscreen: Option<wgpu::Texture>,
+    // A copy of the last presented surface frame, with `GpuConfig::capture_frames`.
+    last_frame: Option<wgpu::Texture>,
+    // Whether the surface or offscreen texture changed size since the last `take_resized`.
+    resized: bool,
+    // The window the surface draws to, only released after the surface is dropped (see `drop_surface`).
+    #[cfg(target_os = "android")]
+    window: Option<crate::Window>,
 }
 impl Gpu {
-    pub async fn new(
-        instance: wgpu::Instance,
-        surface: wgpu::Surface<'static>,
+    /// Creates a device and a surface for `window`, with the first backend of `config` that works.
+    #[cfg(target_os = "android")]
+    pub async fn new(config: &GpuConfig, window: &crate::Window) -> Result<Self, String> {
+        let size = uvec2(window.width() as u32, window.height() as u32);
+        let create_surface = |instance: &_| create_wgpu_surface(instance, window).map(Some);
+        let mut gpu = Self::with_first_backend(config, create_surface, size).await?;
+        gpu.window = Some(window.clone());
+        Ok(gpu)
+    }
+
+    /// Creates a device that draws to a `size` texture instead of a window (eg: for tests, or rendering in the background).
+    /// Needs no window nor Android APIs, so it also runs off Android (eg: `cargo test` on a desktop, with a software adapter).
+    /// The texture is sRGB unless `GpuConfig::srgb` is `Some(false)`.
+    pub async fn new_offscreen(config: &GpuConfig, size: UVec2) -> Result<Self, String> {
+        if size.x == 0 || size.y == 0 {
+            return Err(format!("Invalid offscreen size {size}"));
+        }
+        Self::with_first_backend(config, |_| Ok(None), size).await
+    }
+
+    /// `create_surface` gives the surface to draw to, `None` to draw to an offscreen texture.
+    async fn with_first_backend(
+        config: &GpuConfig,
+        create_surface: impl Fn(&wgpu::Instance) -> Result<Option<wgpu::Surface<'static>>, String>,
+        size: UVec2,
+    ) -> Result<Self, String> {
+        let mut errors = vec![];
+        for backends in &config.backends {
+            match Self::with_backends(config, *backends, &create_surface, size).await {
+                Ok(gpu) => return Ok(gpu),
+                Err(err) => {
+                    log::warn!("Failed to create GPU with {backends:?} : {err}");
+                    errors.push(format!("{backends:?} : {err}"));
+                }
+            }
+        }
+        Err(format!("No usable GPU backend ({})", errors.join(", ")))
+    }
+
+    async fn with_backends(
+        config: &GpuConfig,
+        backends: wgpu::Backends,
+        create_surface: impl Fn(&wgpu::Instance) -> Result<Option<wgpu::Surface<'static>>, String>,
         size: UVec2,
-    ) -> Self {
+    ) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+        let surface = create_surface(&instance)?;
+
         // Handle to the graphics device
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                power_preference: config.power_preference,
+                compatible_surface: surface.as_ref(),
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
+            .ok_or("No compatible adapter")?;
+
+        let missing_features = config.required_features - adapter.features();
+        if !missing_features.is_empty() {
+            Err(format!("Adapter doesn't support {missing_features:?}"))?
+        }
+        let features = config.required_features | (config.optional_features & adapter.features());
 
         // device: Open connection to graphics device
         // queue: Handle to a command queue on the device
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::default(),
-                    required_limits: adapter.limits(),
+                    required_features: features,
+                    required_limits: config.limits.clone().unwrap_or_else(|| adapter.limits()),
                     label: None,
                 },
                 None,
             )
             .await
-            .unwrap();
+            .map_err(|err| format!("Failed to create device : {err}"))?;
 
-        let surface_config = surface
-            .get_default_config(&adapter, size[0], size[1])
-            .unwrap();
-        surface.configure(&device, &surface_config);
+        let (surface_config, offscreen) = match &surface {
+            Some(surface) => {
+                let surface_config =
+                    config.surface_config(&surface.get_capabilities(&adapter), size)?;
+                surface.configure(&device, &surface_config);
+                (surface_config, None)
+            }
+            None => {
+                let surface_config = config.offscreen_config(size);
+                let texture = create_offscreen_texture(&device, &surface_config);
+                (surface_config, Some(texture))
+            }
+        };
 
-        Self {
-            surface: std::mem::ManuallyDrop::new(surface),
+        Ok(Self {
+            instance,
+            adapter,
+            surface,
             device,
             surface_config,
             queue,
+            offscreen,
+            last_frame: None,
+            resized: false,
+            #[cfg(target_os = "android")]
+            window: None,
+        })
+    }
+
+    /// Whether there's nothing to draw to, until `resume` or `resume_offscreen`.
+    pub fn is_suspended(&self) -> bool {
+        self.surface.is_none() && self.offscreen.is_none()
+    }
+
+    /// Drops the surface and the reference to its window, or the offscreen texture.
+    /// Must be called on `TerminateWindow`, before the window is released.
+    pub fn suspend(&mut self) {
+        #[cfg(target_os = "android")]
+        drop_surface(&mut self.surface, &mut self.window);
+        #[cfg(not(target_os = "android"))]
+        {
+            self.surface = None;
+        }
+        self.offscreen = None;
+    }
+
+    /// Draws to a new `size` offscreen texture, instead of a surface (eg: while the app has no window).
+    /// The texture keeps the format of the previous surface or texture.
+    pub fn resume_offscreen(&mut self, size: UVec2) -> Result<(), String> {
+        if size.x == 0 || size.y == 0 {
+            return Err(format!("Invalid offscreen size {size}"));
+        }
+        self.suspend();
+        self.surface_config.usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::TEXTURE_BINDING;
+        self.resized |= size != self.surface_size();
+        self.surface_config.width = size.x;
+        self.surface_config.height = size.y;
+        self.offscreen = Some(create_offscreen_texture(&self.device, &self.surface_config));
+        Ok(())
+    }
+
+    /// Creates a surface for a new `window` (eg: after the app came back from the background),
+    /// keeping the device, queue and everything created with them.
+    /// Fails if the new surface doesn't support the format of the previous one.
+    #[cfg(target_os = "android")]
+    pub fn resume(&mut self, window: &crate::Window) -> Result<(), String> {
+        self.suspend();
+        let surface = create_wgpu_surface(&self.instance, window)?;
+        let format = self.surface_config.format;
+        let capabilities = surface.get_capabilities(&self.adapter);
+        if !capabilities.formats.contains(&format) {
+            return Err(format!("New surface doesn't support format {format:?}"));
         }
+        let capture_frames = self
+            .surface_config
+            .usage
+            .contains(wgpu::TextureUsages::COPY_SRC);
+        self.surface_config.usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if capture_frames {
+            match capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+                true => self.surface_config.usage |= wgpu::TextureUsages::COPY_SRC,
+                false => log::warn!("New surface can't be copied from, frames can't be captured"),
+            }
+        }
+        self.offscreen = None;
+        let size = uvec2(window.width() as u32, window.height() as u32);
+        self.resized |= size != self.surface_size();
+        self.surface_config.width = window.width() as u32;
+        self.surface_config.height = window.height() as u32;
+        surface.configure(&self.device, &self.surface_config);
+        self.surface = Some(surface);
+        self.window = Some(window.clone());
+        Ok(())
     }
 
     pub fn resize(&mut self, new_size: UVec2) {
+        self.resized |= new_size != self.surface_size();
         self.surface_config.width = new_size[0];
         self.surface_config.height = new_size[1];
-        self.surface.configure(&self.device, &self.surface_config);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config);
+        }
+        if self.offscreen.is_some() {
+            self.offscreen = Some(create_offscreen_texture(&self.device, &self.surface_config));
+        }
+    }
+
+    /// The size of the window the surface draws to, `None` while suspended and for an offscreen `Gpu`.
+    pub fn window_size(&self) -> Option<UVec2> {
+        #[cfg(target_os = "android")]
+        if let Some(window) = &self.window {
+            return Some(uvec2(window.width() as u32, window.height() as u32));
+        }
+        None
+    }
+
+    /// Reconfigures the surface if the window's size changed (eg: after a rotation).
+    /// Returns true if the surface was resized. `get_output` already calls it, see `take_resized` to be notified.
+    pub fn update_size(&mut self) -> bool {
+        match self.window_size() {
+            Some(size) if size != self.surface_size() && size.x > 0 && size.y > 0 => {
+                log::info!("Resizing surface to {size}");
+                self.resize(size);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The new size of the surface (or offscreen texture) if it changed since the last call,
+    /// eg: after a rotation, an `Outdated` surface or `resume` with a window of another size.
+    /// Call it after `get_output`, to update what depends on the size (`EguiApp::on_resized` is called from it).
+    pub fn take_resized(&mut self) -> Option<UVec2> {
+        std::mem::take(&mut self.resized).then(|| self.surface_size())
     }
 
     pub fn create_command_encoder(&self) -> wgpu::CommandEncoder {
@@ -81,11 +437,423 @@ impl Gpu {
         uvec2(self.surface_config.width, self.surface_config.height)
     }
 
-    pub fn get_output(
-        &self,
-    ) -> Result<(wgpu::SurfaceTexture, wgpu::TextureView), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+    /// The next texture to draw to, after resizing the surface to the window if needed.
+    /// The surface is reconfigured when it's `Outdated`, and recreated when it's `Lost`.
+    /// `Timeout` and `OutOfMemory` are returned, the frame should be skipped then.
+    /// For an offscreen `Gpu`, it's always the offscreen texture.
+    pub fn get_output(&mut self) -> Result<(Frame, wgpu::TextureView), wgpu::SurfaceError> {
+        if let Some(texture) = &self.offscreen {
+            let view = texture.create_view(&Default::default());
+            return Ok((
+                Frame {
+                    surface_texture: None,
+                },
+                view,
+            ));
+        }
+        self.update_size();
+        let output = match self.current_texture() {
+            Err(wgpu::SurfaceError::Outdated) => {
+                log::info!("Surface outdated, reconfiguring it");
+                self.resize(self.window_size().unwrap_or(self.surface_size()));
+                self.current_texture()?
+            }
+            #[cfg(target_os = "android")]
+            Err(wgpu::SurfaceError::Lost) => {
+                log::warn!("Surface lost, recreating it");
+                let Some(window) = self.window.clone() else {
+                    return Err(wgpu::SurfaceError::Lost);
+                };
+                if let Err(err) = self.resume(&window) {
+                    log::error!("Failed to recreate surface : {err}");
+                    return Err(wgpu::SurfaceError::Lost);
+                }
+                self.current_texture()?
+            }
+            result => result?,
+        };
         let view = output.texture.create_view(&Default::default());
-        Ok((output, view))
+        Ok((
+            Frame {
+                surface_texture: Some(output),
+            },
+            view,
+        ))
+    }
+
+    /// Shows `frame` on the surface, once the commands drawing it are submitted.
+    /// With `GpuConfig::capture_frames`, a copy of it is kept for `capture_frame`.
+    pub fn present(&mut self, frame: Frame) {
+        let Some(output) = frame.surface_texture else {
+            return;
+        };
+        if self
+            .surface_config
+            .usage
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            self.keep_frame(&output.texture);
+        }
+        output.present();
+    }
+
+    /// The last presented frame (or the offscreen texture), as RGBA.
+    /// Waits for the GPU to finish drawing it.
+    /// Needs `GpuConfig::capture_frames` to capture a surface's frames.
+    pub fn capture_frame(&self) -> Result<Picture, String> {
+        let texture = match (&self.offscreen, &self.last_frame) {
+            (Some(texture), _) | (None, Some(texture)) => texture,
+            _ if self
+                .surface_config
+                .usage
+                .contains(wgpu::TextureUsages::COPY_SRC) =>
+            {
+                return Err("No frame was presented yet".to_string());
+            }
+            _ => return Err("Frames aren't kept, see GpuConfig::capture_frames".to_string()),
+        };
+        read_texture(&self.device, &self.queue, texture)
+    }
+
+    // Copies `texture` (the frame being presented) to `last_frame`.
+    fn keep_frame(&mut self, texture: &wgpu::Texture) {
+        let matches = |last: &wgpu::Texture| {
+            last.size() == texture.size() && last.format() == texture.format()
+        };
+        if !self.last_frame.as_ref().is_some_and(matches) {
+            self.last_frame = Some(self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("jano::Gpu last frame"),
+                size: texture.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: texture.format(),
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            }));
+        }
+        let last_frame = self.last_frame.as_ref().unwrap();
+        let mut encoder = self.create_command_encoder();
+        encoder.copy_texture_to_texture(
+            texture.as_image_copy(),
+            last_frame.as_image_copy(),
+            texture.size(),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    fn current_texture(&self) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
+        match &self.surface {
+            Some(surface) => surface.get_current_texture(),
+            None => Err(wgpu::SurfaceError::Lost),
+        }
+    }
+}
+
+impl Drop for Gpu {
+    fn drop(&mut self) {
+        self.suspend();
+    }
+}
+
+/// Drops `surface`, then releases the `window` it draws to: dropping a surface after its window was released crashes.
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+fn drop_surface<S, W>(surface: &mut Option<S>, window: &mut Option<W>) {
+    *surface = None;
+    *window = None;
+}
+
+fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("jano::Gpu offscreen"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
+}
+
+/// Copies an RGBA or BGRA `texture` back from the GPU, as RGBA. Blocks until it's done.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Picture, String> {
+    use wgpu::TextureFormat as F;
+    let bgra = match texture.format() {
+        F::Rgba8Unorm | F::Rgba8UnormSrgb => false,
+        F::Bgra8Unorm | F::Bgra8UnormSrgb => true,
+        format => return Err(format!("Can't read back textures of format {format:?}")),
+    };
+    let size = uvec2(texture.width(), texture.height());
+    // Rows of the copy have to be aligned
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let row_bytes = size.x * 4;
+    let padded_row_bytes = row_bytes.div_ceil(align) * align;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("jano::read_texture"),
+        size: (padded_row_bytes * size.y) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(size.y),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    match receiver.recv() {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => return Err(format!("Failed to read texture : {err}")),
+        Err(_) => return Err("Texture read back was cancelled".to_string()),
+    }
+
+    let mapped = slice.get_mapped_range();
+    let mut data = Vec::with_capacity((row_bytes * size.y) as usize);
+    for row in mapped.chunks(padded_row_bytes as usize) {
+        data.extend_from_slice(&row[..row_bytes as usize]);
+    }
+    drop(mapped);
+    buffer.unmap();
+    if bgra {
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    Ok(Picture { data, size })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An offscreen `Gpu` on any backend.
+    /// Fails the test if the machine has no usable adapter (eg: no GPU nor software renderer),
+    /// unless `JANO_SKIP_GPU_TESTS` is set, then it's `None` and the test is skipped.
+    pub(crate) fn offscreen_gpu(config: GpuConfig, size: UVec2) -> Option<Gpu> {
+        let config = config.backends([wgpu::Backends::all()]);
+        match pollster::block_on(Gpu::new_offscreen(&config, size)) {
+            Ok(gpu) => Some(gpu),
+            Err(err) if std::env::var_os("JANO_SKIP_GPU_TESTS").is_some() => {
+                eprintln!("Skipping GPU test : {err}");
+                None
+            }
+            Err(err) => {
+                panic!("{err}, set JANO_SKIP_GPU_TESTS=1 to skip the tests that need a GPU")
+            }
+        }
+    }
+
+    /// The textures the app holds, as counted by wgpu.
+    fn live_textures(gpu: &Gpu) -> usize {
+        let report = gpu.instance.generate_report().unwrap();
+        let backend = gpu.adapter.get_info().backend;
+        report.hub_report(backend).textures.num_kept_from_user
+    }
+
+    fn clear(gpu: &mut Gpu, color: wgpu::Color) {
+        let (frame, view) = gpu.get_output().unwrap();
+        let mut encoder = gpu.create_command_encoder();
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        gpu.present(frame);
+    }
+
+    #[test]
+    fn suspend_drops_the_target() {
+        let size = uvec2(64, 32);
+        let Some(mut gpu) = offscreen_gpu(GpuConfig::default(), size) else {
+            return;
+        };
+        let textures = live_textures(&gpu);
+        for _ in 0..300 {
+            gpu.suspend();
+            assert!(gpu.is_suspended());
+            assert!(gpu.surface.is_none() && gpu.offscreen.is_none());
+            assert_eq!(live_textures(&gpu), textures - 1);
+            assert!(gpu.get_output().is_err());
+
+            gpu.resume_offscreen(size).unwrap();
+            assert!(!gpu.is_suspended());
+            assert_eq!(live_textures(&gpu), textures);
+            clear(&mut gpu, wgpu::Color::BLUE);
+        }
+        assert_eq!(gpu.capture_frame().unwrap().size, size);
+        assert!(gpu.resume_offscreen(uvec2(0, 32)).is_err());
+    }
+
+    #[test]
+    fn surfaces_are_dropped_before_their_window() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct FakeWindow(Rc<Cell<bool>>);
+        impl Drop for FakeWindow {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+        struct FakeSurface {
+            window_released: Rc<Cell<bool>>,
+            live: Rc<Cell<u32>>,
+        }
+        impl Drop for FakeSurface {
+            fn drop(&mut self) {
+                assert!(
+                    !self.window_released.get(),
+                    "Surface dropped after its window"
+                );
+                self.live.set(self.live.get() - 1);
+            }
+        }
+
+        let live = Rc::new(Cell::new(0));
+        let (mut surface, mut window) = (None, None);
+        for _ in 0..300 {
+            let window_released = Rc::new(Cell::new(false));
+            window = Some(FakeWindow(window_released.clone()));
+            surface = Some(FakeSurface {
+                window_released: window_released.clone(),
+                live: live.clone(),
+            });
+            live.set(live.get() + 1);
+
+            drop_surface(&mut surface, &mut window);
+            assert!(window_released.get());
+            assert_eq!(live.get(), 0);
+        }
+        assert!(surface.is_none() && window.is_none());
+    }
+
+    #[test]
+    fn resizes_are_notified_once() {
+        let Some(mut gpu) = offscreen_gpu(GpuConfig::default(), uvec2(8, 8)) else {
+            return;
+        };
+        assert_eq!(gpu.take_resized(), None);
+        gpu.resize(uvec2(8, 8));
+        assert_eq!(gpu.take_resized(), None);
+
+        gpu.resize(uvec2(16, 4));
+        gpu.get_output().unwrap();
+        assert_eq!(gpu.take_resized(), Some(uvec2(16, 4)));
+        assert_eq!(gpu.take_resized(), None);
+
+        gpu.suspend();
+        gpu.resume_offscreen(uvec2(4, 4)).unwrap();
+        assert_eq!(gpu.take_resized(), Some(uvec2(4, 4)));
+    }
+
+    #[test]
+    fn capture_offscreen_frame() {
+        // The clear color is linear, sRGB targets store it encoded
+        let encode = |c: f64| (1.055 * c.powf(1.0 / 2.4) - 0.055) * 255.0;
+        for (srgb, expected) in [(true, encode(0.2)), (false, 0.2 * 255.0)] {
+            let config = GpuConfig::default().srgb(srgb);
+            let Some(mut gpu) = offscreen_gpu(config, uvec2(5, 3)) else {
+                return;
+            };
+            assert_eq!(gpu.surface_config.format.is_srgb(), srgb);
+            clear(
+                &mut gpu,
+                wgpu::Color {
+                    r: 0.2,
+                    g: 0.0,
+                    b: 1.0,
+                    a: 1.0,
+                },
+            );
+            let pic = gpu.capture_frame().unwrap();
+            assert_eq!(pic.size, uvec2(5, 3));
+            let expected = [expected.round() as u8, 0, 255, 255];
+            for pixel in pic.data.chunks_exact(4) {
+                let close = pixel.iter().zip(expected).all(|(a, b)| a.abs_diff(b) <= 1);
+                assert!(close, "{pixel:?} isn't {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn read_texture_keeps_rows_in_order() {
+        // 70 pixels wide: rows are padded to 512 bytes when copied back
+        let size = uvec2(70, 3);
+        let Some(gpu) = offscreen_gpu(GpuConfig::default(), size) else {
+            return;
+        };
+        let data: Vec<u8> = (0..size.x * size.y * 4).map(|i| (i % 251) as u8).collect();
+        for format in [
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureFormat::Bgra8Unorm,
+        ] {
+            let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            gpu.queue.write_texture(
+                texture.as_image_copy(),
+                &data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size.x * 4),
+                    rows_per_image: Some(size.y),
+                },
+                texture.size(),
+            );
+            let pic = read_texture(&gpu.device, &gpu.queue, &texture).unwrap();
+            assert_eq!(pic.size, size);
+            let mut expected = data.clone();
+            if format == wgpu::TextureFormat::Bgra8Unorm {
+                expected
+                    .chunks_exact_mut(4)
+                    .for_each(|pixel| pixel.swap(0, 2));
+            }
+            assert!(pic.data == expected, "{format:?} read back differently");
+        }
     }
 }
@@ -0,0 +1,1283 @@
+//! A 2D immediate-mode renderer for apps that don't use egui: shapes, pictures and text.
+//!
+//! Everything drawn between `Canvas::begin_frame` and `Canvas::render` is batched into as few draw calls as possible.
+//! Positions and sizes are in points, which `Canvas::scale_factor` turns into pixels,
+//! and shapes are anti-aliased in the shader, so no multisampling is needed.
+
+use super::Gpu;
+use crate::{wgpu, Picture};
+use ab_glyph::{Font as _, ScaleFont as _};
+use glam::{uvec2, vec2, UVec2, Vec2};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// The fonts used for text unless `Canvas::set_font` is called, the first one found is used.
+pub const SYSTEM_FONTS: [&str; 2] = [
+    "/system/fonts/Roboto-Regular.ttf",
+    "/system/fonts/DroidSans.ttf",
+];
+
+// The glyph atlas starts at `ATLAS_SIZE` pixels square, and doubles when full up to `MAX_ATLAS_SIZE`.
+const ATLAS_SIZE: u32 = 1024;
+const MAX_ATLAS_SIZE: u32 = 4096;
+// The white pixels of the atlas, sampled by untextured shapes.
+const WHITE_SIZE: u32 = 2;
+// How far the quad of a shape extends past its edges, in pixels, for anti-aliasing.
+const AA_MARGIN: f32 = 1.0;
+
+/// An sRGB color, with unmultiplied alpha.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+impl Color {
+    pub const TRANSPARENT: Self = Self::rgba(0, 0, 0, 0);
+    pub const BLACK: Self = Self::rgb(0, 0, 0);
+    pub const WHITE: Self = Self::rgb(255, 255, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::rgba(r, g, b, 255)
+    }
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self { a, ..self }
+    }
+
+    fn to_array(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    fn to_wgpu(self, linear: bool) -> wgpu::Color {
+        let channel = |c: u8| {
+            let c = c as f64 / 255.0;
+            match linear {
+                true if c <= 0.04045 => c / 12.92,
+                true => ((c + 0.055) / 1.055).powf(2.4),
+                false => c,
+            }
+        };
+        wgpu::Color {
+            r: channel(self.r),
+            g: channel(self.g),
+            b: channel(self.b),
+            a: self.a as f64 / 255.0,
+        }
+    }
+}
+
+/// An axis-aligned rectangle.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Rect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+impl Rect {
+    pub fn from_min_max(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+    pub fn from_min_size(min: Vec2, size: Vec2) -> Self {
+        Self::from_min_max(min, min + size)
+    }
+    pub fn from_center_size(center: Vec2, size: Vec2) -> Self {
+        Self::from_min_max(center - size / 2.0, center + size / 2.0)
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) / 2.0
+    }
+    pub fn contains(&self, pos: Vec2) -> bool {
+        pos.cmpge(self.min).all() && pos.cmple(self.max).all()
+    }
+    /// Moves every edge `amount` towards the center (away from it if negative).
+    pub fn shrink(self, amount: f32) -> Self {
+        Self::from_min_max(self.min + amount, self.max - amount)
+    }
+    pub fn intersect(self, other: Self) -> Self {
+        Self::from_min_max(self.min.max(other.min), self.max.min(other.max))
+    }
+
+    fn scaled(self, factor: f32) -> Self {
+        Self::from_min_max(self.min * factor, self.max * factor)
+    }
+}
+
+/// A texture created with `Canvas::add_picture`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureId(u64);
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Vertex {
+    // In pixels
+    pos: [f32; 2],
+    uv: [f32; 2],
+    color: [u8; 4],
+    // Position relative to the center of the shape, along its axes, in pixels
+    local: [f32; 2],
+    // Half size, corner radius and stroke width, see canvas.wgsl
+    shape: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Globals {
+    size: [f32; 2],
+    srgb: u32,
+    _padding: u32,
+}
+
+// Consecutive quads with the same texture and clip rect, drawn with one draw call.
+struct Batch {
+    // `None` for the glyph atlas, which also has the white pixels of untextured shapes
+    texture: Option<TextureId>,
+    indices: Range<u32>,
+    clip: Option<[u32; 4]>,
+}
+
+struct Texture {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    size: UVec2,
+}
+
+#[derive(Clone, Copy)]
+struct Glyph {
+    // Position in the atlas
+    min: UVec2,
+    size: UVec2,
+    // From the origin of the glyph (on the baseline) to its top-left corner
+    offset: Vec2,
+}
+
+// The rasterized glyphs, white with the coverage as alpha, packed in rows.
+//
+// The quads of a frame point into it, so it's only ever cleared between frames:
+// when a glyph doesn't fit, it grows, and once it can't anymore, it's cleared when the next frame begins.
+struct GlyphAtlas {
+    pixels: Vec<u8>,
+    size: u32,
+    max_size: u32,
+    // By glyph and pixel size, `None` for glyphs with nothing to draw (eg: spaces)
+    glyphs: HashMap<(ab_glyph::GlyphId, u32), Option<Glyph>>,
+    cursor: UVec2,
+    row_height: u32,
+    // The rows changed since the last upload
+    dirty: Option<Range<u32>>,
+    // Whether to clear it when the next frame begins, because it's full or its glyphs are from another font
+    stale: bool,
+}
+impl GlyphAtlas {
+    fn new(max_size: u32) -> Self {
+        let mut atlas = Self {
+            pixels: vec![],
+            size: ATLAS_SIZE.min(max_size),
+            max_size,
+            glyphs: HashMap::new(),
+            cursor: UVec2::ZERO,
+            row_height: 0,
+            dirty: None,
+            stale: false,
+        };
+        atlas.clear();
+        atlas
+    }
+
+    fn clear(&mut self) {
+        let size = self.size;
+        self.pixels.clear();
+        self.pixels.resize((size * size * 4) as usize, 0);
+        self.glyphs.clear();
+        for y in 0..WHITE_SIZE {
+            for x in 0..WHITE_SIZE {
+                let i = ((y * size + x) * 4) as usize;
+                self.pixels[i..i + 4].copy_from_slice(&[255; 4]);
+            }
+        }
+        self.cursor = uvec2(WHITE_SIZE + 1, 0);
+        self.row_height = WHITE_SIZE;
+        self.dirty = Some(0..size);
+        self.stale = false;
+    }
+
+    // Doubles the size, keeping the glyphs where they are, so only their UVs change.
+    fn grow(&mut self) -> bool {
+        let size = self.size * 2;
+        if size > self.max_size {
+            return false;
+        }
+        log::info!("Glyph atlas is full, growing it to {size}x{size}");
+        let mut pixels = vec![0; (size * size * 4) as usize];
+        let old_row = (self.size * 4) as usize;
+        for (y, row) in self.pixels.chunks_exact(old_row).enumerate() {
+            let start = y * size as usize * 4;
+            pixels[start..start + old_row].copy_from_slice(row);
+        }
+        self.pixels = pixels;
+        self.size = size;
+        self.dirty = Some(0..size);
+        true
+    }
+
+    fn white_uv(&self) -> Vec2 {
+        Vec2::splat(WHITE_SIZE as f32 / 2.0 / self.size as f32)
+    }
+
+    fn glyph(&mut self, font: &ab_glyph::FontVec, id: ab_glyph::GlyphId, px: f32) -> Option<Glyph> {
+        let key = (id, px.to_bits());
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+        let outline = font.outline_glyph(id.with_scale(px));
+        let size = outline.as_ref().map(|outline| {
+            let bounds = outline.px_bounds();
+            uvec2(bounds.width() as u32, bounds.height() as u32)
+        });
+        let glyph = match (outline, size) {
+            (Some(outline), Some(size)) if size.x > 0 && size.y > 0 => {
+                if size.x + 1 >= self.max_size || size.y + 1 >= self.max_size {
+                    log::warn!("Glyph {id:?} is too big for the atlas ({size})");
+                    return None;
+                }
+                let min = loop {
+                    if let Some(min) = self.allocate(size) {
+                        break min;
+                    }
+                    if !self.grow() {
+                        // Not cached, so it's drawn once the atlas is cleared
+                        if !self.stale {
+                            log::info!("Glyph atlas is full, clearing it for the next frame");
+                            self.stale = true;
+                        }
+                        return None;
+                    }
+                };
+                let (pixels, width) = (&mut self.pixels, self.size);
+                outline.draw(|x, y, coverage| {
+                    let i = (((min.y + y) * width + min.x + x) * 4) as usize;
+                    pixels[i..i + 4].copy_from_slice(&[255, 255, 255, (coverage * 255.0) as u8]);
+                });
+                let bounds = outline.px_bounds();
+                Some(Glyph {
+                    min,
+                    size,
+                    offset: vec2(bounds.min.x, bounds.min.y),
+                })
+            }
+            _ => None,
+        };
+        self.glyphs.insert(key, glyph);
+        glyph
+    }
+
+    // Finds room for `size` pixels, with a pixel between glyphs so they don't bleed into each other.
+    fn allocate(&mut self, size: UVec2) -> Option<UVec2> {
+        if self.cursor.x + size.x > self.size {
+            self.cursor = uvec2(0, self.cursor.y + self.row_height + 1);
+            self.row_height = 0;
+        }
+        if self.cursor.x + size.x > self.size || self.cursor.y + size.y > self.size {
+            return None;
+        }
+        let min = self.cursor;
+        self.cursor.x += size.x + 1;
+        self.row_height = self.row_height.max(size.y);
+        let rows = min.y..min.y + size.y;
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(rows.start)..dirty.end.max(rows.end),
+            None => rows,
+        });
+        Some(min)
+    }
+
+    fn upload(&mut self, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        let Some(rows) = self.dirty.take() else {
+            return;
+        };
+        let bytes = (rows.start * self.size * 4) as usize..(rows.end * self.size * 4) as usize;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: rows.start,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.pixels[bytes],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.size * 4),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: self.size,
+                height: rows.end - rows.start,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// Draws 2D shapes, pictures and text to a `Gpu`'s surface.
+///
+/// Every frame, call `begin_frame`, then the drawing methods, then `render`:
+/// ```ignore
+/// let (output, view) = gpu.get_output()?;
+/// canvas.begin_frame(gpu.surface_size());
+/// let area = canvas.safe_rect().shrink(16.0);
+/// canvas.fill_rounded_rect(area, 8.0, Color::rgb(40, 40, 40));
+/// canvas.text(area.min + 8.0, 24.0, Color::WHITE, "Hello");
+/// let mut encoder = gpu.create_command_encoder();
+/// canvas.render(gpu, &mut encoder, &view, Some(Color::BLACK));
+/// gpu.queue.submit([encoder.finish()]);
+/// gpu.present(output);
+/// ```
+pub struct Canvas {
+    pipeline: wgpu::RenderPipeline,
+    globals: wgpu::Buffer,
+    globals_bind_group: wgpu::BindGroup,
+    texture_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    atlas: GlyphAtlas,
+    atlas_sampler: wgpu::Sampler,
+    atlas_texture: wgpu::Texture,
+    atlas_bind_group: wgpu::BindGroup,
+    textures: HashMap<TextureId, Texture>,
+    next_texture_id: u64,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    batches: Vec<Batch>,
+    // In pixels: x, y, width, height
+    clip: Option<[u32; 4]>,
+    font: Option<ab_glyph::FontVec>,
+    // Whether the system fonts were tried, so it's only done once
+    system_font_tried: bool,
+    // Whether the target is sRGB, so colors have to be converted to linear
+    srgb: bool,
+    target_size: UVec2,
+    density: f32,
+    scale_factor_override: Option<f32>,
+}
+impl Canvas {
+    /// Creates a canvas that draws to the surface of `gpu` (or any target with the same format).
+    pub fn new(gpu: &Gpu) -> Self {
+        let device = &gpu.device;
+        let format = gpu.surface_config.format;
+
+        let globals = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("jano::Canvas globals"),
+            size: std::mem::size_of::<Globals>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let globals_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("jano::Canvas globals"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let globals_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("jano::Canvas globals"),
+            layout: &globals_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: globals.as_entire_binding(),
+            }],
+        });
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("jano::Canvas texture"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("jano::Canvas"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("canvas.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("jano::Canvas"),
+            bind_group_layouts: &[&globals_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("jano::Canvas"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                #[cfg(feature = "wgpu_20")]
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Unorm8x4,
+                        3 => Float32x2,
+                        4 => Float32x4,
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                #[cfg(feature = "wgpu_20")]
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("jano::Canvas"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        // Glyphs are drawn pixel for pixel, and the white pixels must not be blended with their neighbours
+        let atlas_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("jano::Canvas atlas"),
+            ..Default::default()
+        });
+        let atlas = GlyphAtlas::new(device.limits().max_texture_dimension_2d.min(MAX_ATLAS_SIZE));
+        let atlas_texture = create_atlas_texture(device, atlas.size);
+        let atlas_bind_group =
+            texture_bind_group(device, &texture_layout, &atlas_texture, &atlas_sampler);
+
+        let mut canvas = Self {
+            pipeline,
+            globals,
+            globals_bind_group,
+            texture_layout,
+            sampler,
+            atlas,
+            atlas_sampler,
+            atlas_texture,
+            atlas_bind_group,
+            textures: HashMap::new(),
+            next_texture_id: 0,
+            vertex_buffer: create_buffer(device, 0, wgpu::BufferUsages::VERTEX),
+            index_buffer: create_buffer(device, 0, wgpu::BufferUsages::INDEX),
+            vertices: vec![],
+            indices: vec![],
+            batches: vec![],
+            clip: None,
+            font: None,
+            system_font_tried: false,
+            srgb: format.is_srgb(),
+            target_size: gpu.surface_size(),
+            density: 1.0,
+            scale_factor_override: None,
+        };
+        // Offscreen targets aren't on a display, and might not be on Android
+        if gpu.offscreen.is_none() {
+            canvas.refresh_density();
+        }
+        canvas
+    }
+
+    /// The number of pixels per point: the display density, unless overridden with `set_scale_factor_override`.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor_override.unwrap_or(self.density)
+    }
+
+    /// Overrides the display density (eg: to draw in pixels, with 1.0), or goes back to it if `None`.
+    pub fn set_scale_factor_override(&mut self, factor: Option<f32>) {
+        self.scale_factor_override = factor.filter(|factor| *factor > 0.0);
+    }
+
+    /// Reads the display density again (eg: on `MainEvent::ConfigChanged`). Does nothing off Android.
+    pub fn refresh_density(&mut self) {
+        #[cfg(target_os = "android")]
+        match crate::display_density() {
+            Ok(density) if density > 0.0 => self.density = density,
+            Ok(density) => log::warn!("Ignoring invalid display density {density}"),
+            Err(err) => log::warn!("Failed to get display density: {err}"),
+        }
+    }
+
+    /// Uses the font in `data` (a `.ttf` or `.otf` file) for text, instead of the system's.
+    pub fn set_font(&mut self, data: Vec<u8>) -> Result<(), String> {
+        let font = ab_glyph::FontVec::try_from_vec(data)
+            .map_err(|err| format!("Failed to load font : {err}"))?;
+        self.font = Some(font);
+        // The glyphs of the previous font might have been drawn this frame
+        self.atlas.glyphs.clear();
+        self.atlas.stale = true;
+        Ok(())
+    }
+
+    /// Starts a frame for a target of `size` pixels (usually `Gpu::surface_size`), discarding what wasn't rendered.
+    pub fn begin_frame(&mut self, size: UVec2) {
+        self.target_size = size;
+        if self.atlas.stale {
+            self.atlas.clear();
+        }
+        self.vertices.clear();
+        self.indices.clear();
+        self.batches.clear();
+        self.clip = None;
+    }
+
+    /// The whole target, in points.
+    pub fn screen_rect(&self) -> Rect {
+        Rect::from_min_size(Vec2::ZERO, self.target_size.as_vec2() / self.scale_factor())
+    }
+
+    /// The part of the target that isn't covered by display cutouts or system bars, in points.
+    pub fn safe_rect(&self) -> Rect {
+        let (min, max) = crate::display_cutout(self.target_size.as_vec2());
+        let scale = self.scale_factor();
+        Rect::from_min_max(min / scale, max / scale)
+    }
+
+    /// Only draws what's drawn next inside `rect`, or everywhere if `None`.
+    pub fn set_clip_rect(&mut self, rect: Option<Rect>) {
+        let scale = self.scale_factor();
+        let size = self.target_size;
+        self.clip = rect.map(|rect| {
+            let to_pixels = |pos: Vec2| (pos * scale).round().max(Vec2::ZERO).as_uvec2().min(size);
+            let (min, max) = (to_pixels(rect.min), to_pixels(rect.max));
+            let max = max.max(min);
+            [min.x, min.y, max.x - min.x, max.y - min.y]
+        });
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        self.rounded_rect(rect, 0.0, 0.0, color);
+    }
+
+    pub fn fill_rounded_rect(&mut self, rect: Rect, radius: f32, color: Color) {
+        self.rounded_rect(rect, radius, 0.0, color);
+    }
+
+    /// Draws the outline of `rect`, `width` points wide, inside of it.
+    pub fn stroke_rect(&mut self, rect: Rect, radius: f32, width: f32, color: Color) {
+        if width > 0.0 {
+            self.rounded_rect(rect, radius, width, color);
+        }
+    }
+
+    pub fn fill_circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        let rect = Rect::from_center_size(center, Vec2::splat(radius * 2.0));
+        self.rounded_rect(rect, radius, 0.0, color);
+    }
+
+    /// Draws the outline of a circle, `width` points wide, inside of it.
+    pub fn stroke_circle(&mut self, center: Vec2, radius: f32, width: f32, color: Color) {
+        let rect = Rect::from_center_size(center, Vec2::splat(radius * 2.0));
+        self.stroke_rect(rect, radius, width, color);
+    }
+
+    /// Draws a line from `a` to `b`, `width` points wide, with round caps.
+    pub fn line(&mut self, a: Vec2, b: Vec2, width: f32, color: Color) {
+        let scale = self.scale_factor();
+        let (a, b, width) = (a * scale, b * scale, width * scale);
+        let length = a.distance(b);
+        let axis = match length > 0.0 {
+            true => (b - a) / length,
+            false => Vec2::X,
+        };
+        let half = vec2(length + width, width) / 2.0;
+        let white = Rect::from_min_max(self.atlas.white_uv(), self.atlas.white_uv());
+        let shape = [half.x, half.y, width / 2.0, 0.0];
+        self.quad(None, (a + b) / 2.0, axis, half, shape, white, color);
+    }
+
+    /// Draws the whole texture `id` in `rect`, multiplied by `tint` (`Color::WHITE` for the texture as is).
+    pub fn image(&mut self, id: TextureId, rect: Rect, tint: Color) {
+        let uv = Rect::from_min_max(Vec2::ZERO, Vec2::ONE);
+        self.image_uv(id, rect, uv, tint);
+    }
+
+    /// Draws the `uv` part of the texture `id` (from 0 to 1) in `rect`.
+    pub fn image_uv(&mut self, id: TextureId, rect: Rect, uv: Rect, tint: Color) {
+        if !self.textures.contains_key(&id) {
+            log::warn!("Drawing unknown texture {id:?}");
+            return;
+        }
+        let scale = self.scale_factor();
+        let half = rect.size() * scale / 2.0;
+        let shape = [half.x, half.y, 0.0, -1.0];
+        self.quad(
+            Some(id),
+            rect.center() * scale,
+            Vec2::X,
+            half,
+            shape,
+            uv,
+            tint,
+        );
+    }
+
+    /// Draws `text` with its top-left corner at `pos`, breaking lines at `\n`.
+    /// `size` is the height of a line without the gap between lines, in points.
+    /// Returns the rect the text covers.
+    pub fn text(&mut self, pos: Vec2, size: f32, color: Color, text: &str) -> Rect {
+        self.layout_text(pos, size, text, Some(color))
+    }
+
+    /// The size `text` would be drawn with by `text`.
+    pub fn measure_text(&mut self, size: f32, text: &str) -> Vec2 {
+        self.layout_text(Vec2::ZERO, size, text, None).size()
+    }
+
+    /// Creates a texture from `pic`, to draw with `image`.
+    pub fn add_picture(&mut self, gpu: &Gpu, pic: &Picture) -> Result<TextureId, String> {
+        let texture = self.create_texture(gpu, pic)?;
+        let id = TextureId(self.next_texture_id);
+        self.next_texture_id += 1;
+        self.textures.insert(id, texture);
+        Ok(id)
+    }
+
+    /// Replaces the pixels of the texture `id` with `pic`, which can have a different size.
+    pub fn update_picture(
+        &mut self,
+        gpu: &Gpu,
+        id: TextureId,
+        pic: &Picture,
+    ) -> Result<(), String> {
+        let Some(texture) = self.textures.get(&id) else {
+            return Err(format!("Unknown texture {id:?}"));
+        };
+        if texture.size == pic.size {
+            check_picture(pic)?;
+            write_picture(&gpu.queue, &texture.texture, pic);
+        } else {
+            let texture = self.create_texture(gpu, pic)?;
+            self.textures.insert(id, texture);
+        }
+        Ok(())
+    }
+
+    /// Frees the texture `id`. Returns false if it didn't exist.
+    pub fn remove_texture(&mut self, id: TextureId) -> bool {
+        self.textures.remove(&id).is_some()
+    }
+
+    /// The size of the texture `id` in pixels, if it exists.
+    pub fn texture_size(&self, id: TextureId) -> Option<UVec2> {
+        self.textures.get(&id).map(|texture| texture.size)
+    }
+
+    /// Draws everything since `begin_frame` to `view`, after clearing it with `clear` (if `Some`).
+    /// `view` must be `begin_frame`'s size, and the format of the surface of the `Gpu` the canvas was created with.
+    /// Call it once per submitted encoder, as the vertices are written to the same buffers every time.
+    pub fn render(
+        &mut self,
+        gpu: &Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        clear: Option<Color>,
+    ) {
+        if self.atlas_texture.width() != self.atlas.size {
+            self.atlas_texture = create_atlas_texture(&gpu.device, self.atlas.size);
+            self.atlas_bind_group = texture_bind_group(
+                &gpu.device,
+                &self.texture_layout,
+                &self.atlas_texture,
+                &self.atlas_sampler,
+            );
+        }
+        self.atlas.upload(&gpu.queue, &self.atlas_texture);
+        let globals = Globals {
+            size: self.target_size.as_vec2().into(),
+            srgb: self.srgb as u32,
+            _padding: 0,
+        };
+        gpu.queue
+            .write_buffer(&self.globals, 0, as_bytes(std::slice::from_ref(&globals)));
+
+        let vertices = as_bytes(&self.vertices);
+        let indices = as_bytes(&self.indices);
+        if self.vertex_buffer.size() < vertices.len() as u64 {
+            self.vertex_buffer =
+                create_buffer(&gpu.device, vertices.len(), wgpu::BufferUsages::VERTEX);
+        }
+        if self.index_buffer.size() < indices.len() as u64 {
+            self.index_buffer =
+                create_buffer(&gpu.device, indices.len(), wgpu::BufferUsages::INDEX);
+        }
+        if !vertices.is_empty() {
+            gpu.queue.write_buffer(&self.vertex_buffer, 0, vertices);
+            gpu.queue.write_buffer(&self.index_buffer, 0, indices);
+        }
+
+        let load = match clear {
+            Some(color) => wgpu::LoadOp::Clear(color.to_wgpu(self.srgb)),
+            None => wgpu::LoadOp::Load,
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("jano::Canvas"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        if self.batches.is_empty() {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.globals_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        let size = self.target_size;
+        for batch in &self.batches {
+            let bind_group = match batch.texture {
+                None => &self.atlas_bind_group,
+                Some(id) => match self.textures.get(&id) {
+                    Some(texture) => &texture.bind_group,
+                    // Removed since it was drawn
+                    None => continue,
+                },
+            };
+            let [x, y, width, height] = batch.clip.unwrap_or([0, 0, size.x, size.y]);
+            if width == 0 || height == 0 {
+                continue;
+            }
+            pass.set_scissor_rect(x, y, width, height);
+            pass.set_bind_group(1, bind_group, &[]);
+            pass.draw_indexed(batch.indices.clone(), 0, 0..1);
+        }
+    }
+
+    fn rounded_rect(&mut self, rect: Rect, radius: f32, stroke: f32, color: Color) {
+        let scale = self.scale_factor();
+        let half = (rect.size() * scale / 2.0).max(Vec2::ZERO);
+        let radius = (radius * scale).clamp(0.0, half.min_element());
+        let shape = [half.x, half.y, radius, stroke * scale];
+        let white = Rect::from_min_max(self.atlas.white_uv(), self.atlas.white_uv());
+        self.quad(
+            None,
+            rect.center() * scale,
+            Vec2::X,
+            half,
+            shape,
+            white,
+            color,
+        );
+    }
+
+    // Adds a quad centered on `center`, with its x axis along `axis` (normalized), all in pixels.
+    #[allow(clippy::too_many_arguments)]
+    fn quad(
+        &mut self,
+        texture: Option<TextureId>,
+        center: Vec2,
+        axis: Vec2,
+        half: Vec2,
+        shape: [f32; 4],
+        uv: Rect,
+        color: Color,
+    ) {
+        if color.a == 0 {
+            return;
+        }
+        // Textured quads (negative stroke) aren't anti-aliased, and their UVs are for their exact size
+        let extent = match shape[3] >= 0.0 {
+            true => half + AA_MARGIN,
+            false => half,
+        };
+        let normal = axis.perp();
+        let base = self.vertices.len() as u32;
+        let corners = [
+            (vec2(-1.0, -1.0), uv.min),
+            (vec2(1.0, -1.0), vec2(uv.max.x, uv.min.y)),
+            (vec2(1.0, 1.0), uv.max),
+            (vec2(-1.0, 1.0), vec2(uv.min.x, uv.max.y)),
+        ];
+        for (corner, uv) in corners {
+            let local = corner * extent;
+            self.vertices.push(Vertex {
+                pos: (center + axis * local.x + normal * local.y).into(),
+                uv: uv.into(),
+                color: color.to_array(),
+                local: local.into(),
+                shape,
+            });
+        }
+        self.indices
+            .extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+
+        let end = self.indices.len() as u32;
+        match self.batches.last_mut() {
+            Some(batch) if batch.texture == texture && batch.clip == self.clip => {
+                batch.indices.end = end;
+            }
+            _ => self.batches.push(Batch {
+                texture,
+                indices: end - 6..end,
+                clip: self.clip,
+            }),
+        }
+    }
+
+    // Lays out `text` at `pos` and returns the rect it covers, drawing it if `color` is `Some`.
+    fn layout_text(&mut self, pos: Vec2, size: f32, text: &str, color: Option<Color>) -> Rect {
+        self.load_system_font();
+        // Taken so the glyphs can be added while it's borrowed
+        let Some(font) = self.font.take() else {
+            return Rect::from_min_size(pos, Vec2::ZERO);
+        };
+        let scale = self.scale_factor();
+        let px = size * scale;
+        let scaled = font.as_scaled(px);
+        let line_height = scaled.height() + scaled.line_gap();
+        let origin = pos * scale;
+
+        let mut width: f32 = 0.0;
+        let mut lines = 0;
+        for (line_index, line) in text.split('\n').enumerate() {
+            let baseline = (origin.y + scaled.ascent() + line_height * line_index as f32).round();
+            let mut x = origin.x;
+            let mut previous = None;
+            for c in line.chars() {
+                let id = scaled.glyph_id(c);
+                if let Some(previous) = previous {
+                    x += scaled.kern(previous, id);
+                }
+                let glyph = color.and_then(|color| Some((color, self.glyph(&font, id, px)?)));
+                if let Some((color, glyph)) = glyph {
+                    let min = vec2(x.round(), baseline) + glyph.offset;
+                    let half = glyph.size.as_vec2() / 2.0;
+                    let uv = Rect::from_min_size(glyph.min.as_vec2(), glyph.size.as_vec2())
+                        .scaled(1.0 / self.atlas.size as f32);
+                    let shape = [half.x, half.y, 0.0, -1.0];
+                    self.quad(None, min + half, Vec2::X, half, shape, uv, color);
+                }
+                x += scaled.h_advance(id);
+                previous = Some(id);
+            }
+            width = width.max(x - origin.x);
+            lines = line_index + 1;
+        }
+        let height = line_height * (lines - 1) as f32 + scaled.height();
+        self.font = Some(font);
+        Rect::from_min_size(pos, vec2(width, height) / scale)
+    }
+
+    // Adds the glyph to the atlas, fixing the UVs of what was drawn with it if it had to grow.
+    fn glyph(&mut self, font: &ab_glyph::FontVec, id: ab_glyph::GlyphId, px: f32) -> Option<Glyph> {
+        let size = self.atlas.size;
+        let glyph = self.atlas.glyph(font, id, px);
+        if self.atlas.size != size {
+            let factor = size as f32 / self.atlas.size as f32;
+            for batch in self.batches.iter().filter(|batch| batch.texture.is_none()) {
+                // Quads are added in order, so their vertices are contiguous too
+                let first = self.indices[batch.indices.start as usize] as usize;
+                let last = self.indices[batch.indices.end as usize - 1] as usize;
+                for vertex in &mut self.vertices[first..=last] {
+                    vertex.uv = (Vec2::from(vertex.uv) * factor).into();
+                }
+            }
+        }
+        glyph
+    }
+
+    fn load_system_font(&mut self) {
+        if self.font.is_some() || self.system_font_tried {
+            return;
+        }
+        self.system_font_tried = true;
+        for path in SYSTEM_FONTS {
+            let Ok(data) = std::fs::read(path) else {
+                continue;
+            };
+            match self.set_font(data) {
+                Ok(()) => {
+                    log::info!("Using font {path} for the canvas");
+                    return;
+                }
+                Err(err) => log::warn!("{path} : {err}"),
+            }
+        }
+        log::error!("None of {SYSTEM_FONTS:?} could be loaded, no text will be drawn");
+    }
+
+    fn create_texture(&self, gpu: &Gpu, pic: &Picture) -> Result<Texture, String> {
+        check_picture(pic)?;
+        let max = gpu.device.limits().max_texture_dimension_2d;
+        if pic.size.x > max || pic.size.y > max {
+            return Err(format!(
+                "Picture of {} is bigger than the maximum texture size ({max})",
+                pic.size
+            ));
+        }
+        // Sampling an sRGB texture gives linear colors, which an sRGB target expects
+        let format = match self.srgb {
+            true => wgpu::TextureFormat::Rgba8UnormSrgb,
+            false => wgpu::TextureFormat::Rgba8Unorm,
+        };
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("jano::Canvas picture"),
+            size: wgpu::Extent3d {
+                width: pic.size.x,
+                height: pic.size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        write_picture(&gpu.queue, &texture, pic);
+        let bind_group =
+            texture_bind_group(&gpu.device, &self.texture_layout, &texture, &self.sampler);
+        Ok(Texture {
+            texture,
+            bind_group,
+            size: pic.size,
+        })
+    }
+}
+
+fn check_picture(pic: &Picture) -> Result<(), String> {
+    if pic.size.x == 0 || pic.size.y == 0 {
+        return Err(format!("Picture is empty ({})", pic.size));
+    }
+    let expected = pic.size.x as usize * pic.size.y as usize * 4;
+    if pic.data.len() != expected {
+        return Err(format!(
+            "Picture of {} has {} bytes instead of {expected}",
+            pic.size,
+            pic.data.len()
+        ));
+    }
+    Ok(())
+}
+
+fn write_picture(queue: &wgpu::Queue, texture: &wgpu::Texture, pic: &Picture) {
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        &pic.data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(pic.size.x * 4),
+            rows_per_image: Some(pic.size.y),
+        },
+        wgpu::Extent3d {
+            width: pic.size.x,
+            height: pic.size.y,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+fn create_atlas_texture(device: &wgpu::Device, size: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("jano::Canvas atlas"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn texture_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &wgpu::Texture,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    let view = texture.create_view(&Default::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("jano::Canvas texture"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+// A buffer of at least `size` bytes, rounded up so it isn't recreated every time a frame draws a bit more.
+fn create_buffer(device: &wgpu::Device, size: usize, usage: wgpu::BufferUsages) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("jano::Canvas"),
+        size: size.max(1024).next_power_of_two() as u64,
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn as_bytes<T: Copy>(values: &[T]) -> &[u8] {
+    // SAFETY: only used with `repr(C)` types that have no padding
+    unsafe { std::slice::from_raw_parts(values.as_ptr().cast(), std::mem::size_of_val(values)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::tests::offscreen_gpu;
+    use crate::graphics::GpuConfig;
+
+    /// Compares `pic` to the image in `tests/fixtures/golden/{name}.pam`, allowing small differences between GPUs.
+    /// With `JANO_BLESS=1` set, `pic` is saved as the golden image instead.
+    pub(crate) fn assert_golden(name: &str, pic: &Picture) {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden");
+        let path = dir.join(format!("{name}.pam"));
+        if std::env::var_os("JANO_BLESS").is_some() {
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(&path, to_pam(pic)).unwrap();
+            return;
+        }
+        let golden = std::fs::read(&path)
+            .map(|pam| from_pam(&pam))
+            .unwrap_or_else(|err| panic!("Failed to read {path:?} ({err}), run with JANO_BLESS=1"));
+        let differs = golden.size != pic.size
+            || (golden.data.iter().zip(&pic.data)).any(|(a, b)| a.abs_diff(*b) > 4);
+        if differs {
+            let actual = std::env::temp_dir().join(format!("{name}.actual.pam"));
+            std::fs::write(&actual, to_pam(pic)).unwrap();
+            panic!("{name} differs from {path:?}, the captured image is in {actual:?}");
+        }
+    }
+
+    // A PAM (netpbm) image, viewable with most image viewers.
+    fn to_pam(pic: &Picture) -> Vec<u8> {
+        let UVec2 { x, y } = pic.size;
+        let header =
+            format!("P7\nWIDTH {x}\nHEIGHT {y}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n");
+        [header.as_bytes(), &pic.data].concat()
+    }
+
+    fn from_pam(pam: &[u8]) -> Picture {
+        let end = b"ENDHDR\n";
+        let header_len = pam.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+        let header = std::str::from_utf8(&pam[..header_len]).unwrap();
+        let field = |name| {
+            let line = header.lines().find_map(|line| line.strip_prefix(name));
+            line.unwrap().trim().parse().unwrap()
+        };
+        Picture {
+            data: pam[header_len..].to_vec(),
+            size: uvec2(field("WIDTH "), field("HEIGHT ")),
+        }
+    }
+
+    fn draw(gpu: &mut Gpu, draw: impl FnOnce(&mut Canvas, &Gpu)) -> Picture {
+        let mut canvas = Canvas::new(gpu);
+        canvas.set_scale_factor_override(Some(1.0));
+        let (frame, view) = gpu.get_output().unwrap();
+        canvas.begin_frame(gpu.surface_size());
+        draw(&mut canvas, gpu);
+        let mut encoder = gpu.create_command_encoder();
+        canvas.render(gpu, &mut encoder, &view, Some(Color::rgb(32, 32, 32)));
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        gpu.present(frame);
+        gpu.capture_frame().unwrap()
+    }
+
+    fn shapes(canvas: &mut Canvas, gpu: &Gpu) {
+        let red = Color::rgb(220, 40, 40);
+        canvas.fill_rect(Rect::from_min_size(vec2(2.0, 2.0), vec2(12.0, 8.0)), red);
+        let rect = Rect::from_min_size(vec2(18.0, 2.0), vec2(20.0, 14.0));
+        canvas.fill_rounded_rect(rect, 5.0, Color::rgb(40, 160, 60));
+        let rect = Rect::from_min_size(vec2(42.0, 2.0), vec2(20.0, 14.0));
+        canvas.stroke_rect(rect, 3.0, 2.0, Color::WHITE);
+        canvas.fill_circle(vec2(10.0, 30.0), 8.5, Color::rgb(40, 80, 220));
+        canvas.stroke_circle(vec2(30.0, 30.0), 8.0, 1.5, Color::rgb(240, 200, 40));
+        canvas.line(
+            vec2(42.0, 22.0),
+            vec2(60.0, 40.0),
+            2.0,
+            Color::WHITE.with_alpha(128),
+        );
+
+        // A 2x2 checkerboard, stretched with linear filtering
+        let black_white = [[0, 0, 0, 255], [255; 4], [255; 4], [0, 0, 0, 255]];
+        let pic = Picture {
+            data: black_white.concat(),
+            size: uvec2(2, 2),
+        };
+        let id = canvas.add_picture(gpu, &pic).unwrap();
+        canvas.image(
+            id,
+            Rect::from_min_size(vec2(2.0, 40.0), vec2(8.0, 6.0)),
+            Color::WHITE,
+        );
+
+        // Half of the circle is clipped
+        canvas.set_clip_rect(Some(Rect::from_min_max(vec2(12.0, 38.0), vec2(64.0, 43.0))));
+        canvas.fill_circle(vec2(18.0, 43.0), 4.0, red);
+    }
+
+    #[test]
+    fn golden_shapes() {
+        for srgb in [true, false] {
+            let config = GpuConfig::default().srgb(srgb);
+            let Some(mut gpu) = offscreen_gpu(config, uvec2(64, 48)) else {
+                return;
+            };
+            let pic = draw(&mut gpu, shapes);
+            // Colors are sRGB, so both targets look the same, except for blending
+            let name = match srgb {
+                true => "canvas_shapes",
+                false => "canvas_shapes_linear",
+            };
+            assert_golden(name, &pic);
+        }
+    }
+
+    // Hack, from tests/fixtures, as the system fonts are only on Android.
+    fn test_font() -> Vec<u8> {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/Hack-Regular.ttf"
+        );
+        std::fs::read(path).unwrap()
+    }
+
+    fn text(canvas: &mut Canvas, _: &Gpu) {
+        canvas.set_font(test_font()).unwrap();
+        canvas.fill_rect(
+            Rect::from_min_size(vec2(2.0, 2.0), vec2(60.0, 4.0)),
+            Color::WHITE,
+        );
+        let rect = canvas.text(vec2(4.0, 8.0), 14.0, Color::WHITE, "Jano");
+        canvas.stroke_rect(rect, 0.0, 1.0, Color::rgb(220, 40, 40));
+        canvas.text(vec2(4.0, 26.0), 10.0, Color::rgb(240, 200, 40), "ab\ncd");
+    }
+
+    #[test]
+    fn golden_text() {
+        let Some(mut gpu) = offscreen_gpu(GpuConfig::default(), uvec2(64, 48)) else {
+            return;
+        };
+        let pic = draw(&mut gpu, text);
+        assert_golden("canvas_text", &pic);
+    }
+
+    #[test]
+    fn text_survives_the_atlas_growing() {
+        let Some(mut gpu) = offscreen_gpu(GpuConfig::default(), uvec2(64, 48)) else {
+            return;
+        };
+        let pic = draw(&mut gpu, |canvas, gpu| {
+            text(canvas, gpu);
+            // Big glyphs, not visible, until the atlas is full
+            canvas.set_clip_rect(Some(Rect::from_min_size(Vec2::ZERO, Vec2::ZERO)));
+            let mut size = 100.0;
+            while canvas.atlas.size == ATLAS_SIZE {
+                canvas.text(Vec2::ZERO, size, Color::WHITE, "W");
+                size += 1.0;
+            }
+            canvas.set_clip_rect(None);
+        });
+        assert_golden("canvas_text", &pic);
+    }
+
+    #[test]
+    fn full_atlas_is_cleared_between_frames() {
+        let font = ab_glyph::FontVec::try_from_vec(test_font()).unwrap();
+        let id = font.glyph_id('W');
+        let mut atlas = GlyphAtlas::new(ATLAS_SIZE * 2);
+        let first = atlas.glyph(&font, id, 100.0).unwrap();
+        let pixels_of = |atlas: &GlyphAtlas, glyph: Glyph| {
+            let rows = glyph.min.y..glyph.min.y + glyph.size.y;
+            let row = |y| ((y * atlas.size + glyph.min.x) * 4) as usize;
+            let rows =
+                rows.map(|y| atlas.pixels[row(y)..row(y) + glyph.size.x as usize * 4].to_vec());
+            rows.collect::<Vec<_>>()
+        };
+        let pixels = pixels_of(&atlas, first);
+
+        let mut size = 101.0;
+        while atlas.glyph(&font, id, size).is_some() {
+            size += 1.0;
+        }
+        // It grew, then kept what was drawn until the end of the frame
+        assert_eq!(atlas.size, ATLAS_SIZE * 2);
+        assert!(atlas.stale);
+        let glyph = atlas.glyph(&font, id, 100.0).unwrap();
+        assert_eq!((glyph.min, glyph.size), (first.min, first.size));
+        assert_eq!(pixels_of(&atlas, glyph), pixels);
+
+        atlas.clear();
+        assert!(!atlas.stale);
+        assert!(atlas.glyph(&font, id, size).is_some());
+    }
+
+    #[test]
+    fn nothing_drawn() {
+        let Some(mut gpu) = offscreen_gpu(GpuConfig::default(), uvec2(8, 8)) else {
+            return;
+        };
+        let pic = draw(&mut gpu, |_, _| {});
+        assert!(pic
+            .data
+            .chunks_exact(4)
+            .all(|pixel| pixel == [32, 32, 32, 255]));
+    }
+}
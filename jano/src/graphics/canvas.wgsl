@@ -0,0 +1,74 @@
+// Every draw is a quad. Shapes are anti-aliased from the distance to the edge of the rounded rect they describe:
+// `local` is the position relative to the center of the shape, in pixels,
+// and `shape` is its half size, corner radius and stroke width (0 for a filled shape).
+// A negative stroke width draws the texture of the quad as is (pictures, glyphs).
+
+struct Globals {
+    // The size of the target, in pixels.
+    size: vec2<f32>,
+    // Whether the target is sRGB, so the sRGB vertex colors have to be converted to linear.
+    srgb: u32,
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var tex: texture_2d<f32>;
+@group(1) @binding(1) var tex_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) local: vec2<f32>,
+    @location(3) shape: vec4<f32>,
+}
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, srgb < vec3<f32>(0.04045));
+}
+
+@vertex
+fn vs_main(
+    @location(0) pos: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) local: vec2<f32>,
+    @location(4) shape: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        pos.x / globals.size.x * 2.0 - 1.0,
+        1.0 - pos.y / globals.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.uv = uv;
+    out.color = color;
+    if globals.srgb != 0u {
+        out.color = vec4<f32>(linear_from_srgb(color.rgb), color.a);
+    }
+    out.local = local;
+    out.shape = shape;
+    return out;
+}
+
+// Signed distance from `p` to the edge of a rect of half size `half` with corners of radius `radius`, negative inside.
+fn rounded_rect_distance(p: vec2<f32>, half: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(tex, tex_sampler, in.uv) * in.color;
+    var coverage = 1.0;
+    if in.shape.w >= 0.0 {
+        let distance = rounded_rect_distance(in.local, in.shape.xy, in.shape.z);
+        coverage = clamp(0.5 - distance, 0.0, 1.0);
+        if in.shape.w > 0.0 {
+            coverage *= clamp(0.5 + distance + in.shape.w, 0.0, 1.0);
+        }
+    }
+    return vec4<f32>(color.rgb, color.a * coverage);
+}
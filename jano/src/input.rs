@@ -1,23 +1,20 @@
+#[cfg(target_os = "android")]
 use android_activity::{
-    input::{InputEvent, KeyAction, KeyEvent, KeyMapChar, MotionAction},
+    input::{
+        Axis, InputEvent, KeyAction, KeyEvent, KeyMapChar, Keycode, MetaState, MotionAction,
+        MotionEvent, Source, TextInputState, TextSpan, ToolType,
+    },
     InputStatus,
 };
-use glam::{vec2, Vec2};
+#[cfg(target_os = "android")]
+use glam::vec2;
+use glam::Vec2;
 
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::{Duration, SystemTime};
+use crate::gamepad::{GamepadEvent, Gamepads};
 
-// scale * 10 (because atomic floats don't exist)
-static SCALE_FACTOR: AtomicU32 = AtomicU32::new(35);
-pub fn scale_factor() -> f32 {
-    SCALE_FACTOR.load(Ordering::SeqCst) as f32 * 0.1
-}
-pub fn set_scale_factor(factor: f32) {
-    let factor = (factor * 10.0) as u32;
-    if factor > 0 {
-        SCALE_FACTOR.store(factor, Ordering::SeqCst);
-    }
-}
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum PtrButton {
@@ -58,23 +55,309 @@ fn pointers_diff<'a>(prev: &'a [Ptr], new: &'a [Ptr]) -> impl Iterator<Item = Pt
     new_iter.chain(mv_iter).chain(rm_iter)
 }
 
+/// The state of an ongoing two-finger gesture.
+/// Spans are the (non-squared) distances between the two pointers, `anchor` is their midpoint.
 #[derive(Clone, Copy, Debug)]
 pub struct Zoom {
-    pub start_dist: f32,
-    pub prev_dist: f32,
+    pub start_span: f32,
+    pub prev_span: f32,
     pub anchor: Vec2,
 }
+impl Zoom {
+    pub fn new(a: Vec2, b: Vec2) -> Self {
+        let span = a.distance(b);
+        Self {
+            start_span: span,
+            prev_span: span,
+            anchor: (a + b) * 0.5,
+        }
+    }
 
-#[derive(Clone, Debug)]
+    /// Moves the gesture to the new pointer positions `a` and `b`.
+    /// Returns the zoom ratio (`current_span / previous_span`) and the pan delta of the midpoint.
+    pub fn update(&mut self, a: Vec2, b: Vec2) -> (f32, Vec2) {
+        let span = a.distance(b);
+        let anchor = (a + b) * 0.5;
+        let ratio = if self.prev_span > 0.0 && span > 0.0 {
+            span / self.prev_span
+        } else {
+            1.0
+        };
+        let delta = anchor - self.anchor;
+        self.prev_span = span;
+        self.anchor = anchor;
+        (ratio, delta)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Modifiers {
+    pub alt: bool,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub meta: bool,
+}
+#[cfg(target_os = "android")]
+impl From<MetaState> for Modifiers {
+    fn from(state: MetaState) -> Self {
+        Self {
+            alt: state.alt_on(),
+            ctrl: state.ctrl_on(),
+            shift: state.shift_on(),
+            meta: state.meta_on(),
+        }
+    }
+}
+
+/// An input method (IME) event, such as a soft keyboard composing a word.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ImeEvent {
+    Enabled,
+    /// The text currently being composed, replaces the previous `Preedit`.
+    Preedit(String),
+    /// Finishes the composition, replacing the `Preedit` text with this text.
+    Commit(String),
+    Disabled,
+}
+
+/// An edit of the focused text field by Android's soft keyboard, returned by `TextInputSync::take_edit`.
+/// Indices are in chars.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TextFieldEdit {
+    /// The chars to select before the edit, they are replaced by `text` and `compose`.
+    pub replace: Range<usize>,
+    /// The text replacing the selected chars.
+    pub text: String,
+    /// The text being composed, inserted after `text` and left selected (like `ImeEvent::Preedit`).
+    pub compose: Option<String>,
+    /// Whether the text composed before the edit is finished.
+    pub ends_composition: bool,
+    /// The selection to set once the edit is applied, if it isn't the cursor after the inserted text.
+    pub selection: Option<Range<usize>>,
+}
+
+/// Keeps the text input state of Android's soft keyboard in sync with the focused text field.
+///
+/// Android's `TextInputState`s are turned into a single `TextFieldEdit` per frame by `take_edit`,
+/// the contents of the text field are sent back to Android with `sync_from_app`.
+/// Indices are in chars, Android's indices are in UTF-16 code units.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+pub struct TextInputSync {
+    text: String,
+    selection: Range<usize>,
+    compose: Option<Range<usize>>,
+    // Android's latest text, selection and composed region, not applied to the text field yet.
+    pending: Option<(String, Range<usize>, Option<Range<usize>>)>,
+}
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+impl TextInputSync {
+    /// Forgets the current state, for when the text field lost focus.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Updates the state to the contents of the focused text field.
+    /// Returns the state to give to `AndroidApp::set_text_input_state`, if it changed.
+    #[cfg(target_os = "android")]
+    pub fn sync_from_app(&mut self, text: &str, selection: Range<usize>) -> Option<TextInputState> {
+        if !self.set_from_app(text, selection) {
+            return None;
+        }
+        Some(TextInputState {
+            text: self.text.clone(),
+            selection: TextSpan {
+                start: utf16_idx(&self.text, self.selection.start),
+                end: utf16_idx(&self.text, self.selection.end),
+            },
+            compose_region: None,
+        })
+    }
+
+    fn set_from_app(&mut self, text: &str, selection: Range<usize>) -> bool {
+        // While composing, the text field selects the composed text, while Android places the cursor after it.
+        let same_selection =
+            selection == self.selection || Some(&selection) == self.compose.as_ref();
+        if text == self.text && same_selection {
+            return false;
+        }
+        self.set(text.to_owned(), selection, None);
+        true
+    }
+
+    /// Records Android's new state, to be applied by the next `take_edit`.
+    #[cfg(target_os = "android")]
+    pub fn on_text_input_state(&mut self, state: &TextInputState) {
+        let text = state.text.clone();
+        let span = |span: TextSpan| {
+            let (start, end) = (span.start.min(span.end), span.start.max(span.end));
+            char_idx(&text, start)..char_idx(&text, end)
+        };
+        let selection = span(state.selection);
+        let compose = state.compose_region.map(span).filter(|r| !r.is_empty());
+        self.pending = Some((text, selection, compose));
+    }
+
+    /// Returns the edit that turns the text field into Android's latest state, if it changed.
+    /// Call it once per frame, after handling the input events.
+    pub fn take_edit(&mut self) -> Option<TextFieldEdit> {
+        let (text, selection, compose) = self.pending.take()?;
+        let (old_len, new_len) = (self.text.chars().count(), text.chars().count());
+        let (mut start, removed, inserted) = diff_chars(&self.text, &text);
+        if removed == 0 && inserted.is_empty() {
+            if compose == self.compose && (compose.is_some() || selection == self.selection) {
+                self.set(text, selection, compose);
+                return None;
+            }
+            // Only the selection or the composed region changed
+            start = compose.as_ref().map_or(start, |compose| compose.start);
+        }
+        let inserted_end = start + inserted.chars().count();
+        // The text field selects the composed text, so it can only be inserted after everything else
+        let compose = compose.filter(|compose| compose.end >= inserted_end);
+
+        let mut edit = TextFieldEdit {
+            replace: start..start + removed,
+            text: inserted,
+            compose: None,
+            ends_composition: self.compose.is_some() && compose.is_none(),
+            selection: None,
+        };
+        if let Some(compose) = compose.clone() {
+            let from = start.min(compose.start);
+            edit.replace = from..old_len - (new_len - compose.end);
+            edit.text = slice_chars(&text, from..compose.start);
+            edit.compose = Some(slice_chars(&text, compose));
+        } else if selection != (inserted_end..inserted_end) {
+            edit.selection = Some(selection.clone());
+        }
+        self.set(text, selection, compose);
+        Some(edit)
+    }
+
+    fn set(&mut self, text: String, selection: Range<usize>, compose: Option<Range<usize>>) {
+        self.text = text;
+        self.selection = selection;
+        self.compose = compose;
+    }
+}
+
+/// Finds the single edit that turns `old` into `new`.
+/// Returns the char index of the edit, the number of removed chars and the inserted text.
+fn diff_chars(old: &str, new: &str) -> (usize, usize, String) {
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let removed = old.len() - prefix - suffix;
+    let inserted = new[prefix..new.len() - suffix].iter().collect();
+    (prefix, removed, inserted)
+}
+
+fn slice_chars(text: &str, range: Range<usize>) -> String {
+    text.chars().skip(range.start).take(range.len()).collect()
+}
+
+#[cfg(target_os = "android")]
+/// Converts a UTF-16 code unit index (as used by Java) into a char index.
+fn char_idx(text: &str, utf16_idx: usize) -> usize {
+    let mut units = 0;
+    for (idx, ch) in text.chars().enumerate() {
+        if units >= utf16_idx {
+            return idx;
+        }
+        units += ch.len_utf16();
+    }
+    text.chars().count()
+}
+
+#[cfg(target_os = "android")]
+/// Converts a char index into a UTF-16 code unit index (as used by Java).
+fn utf16_idx(text: &str, char_idx: usize) -> usize {
+    text.chars().take(char_idx).map(char::len_utf16).sum()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TouchPhase {
+    Start,
+    Move,
+    End,
+    Cancel,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum TouchEvent {
     Text(String),
-    KeyBackspace { pressed: bool },
+    Ime(ImeEvent),
+    /// A key press or release from a soft or hardware keyboard.
+    /// Text typed with the key is sent separately as `TouchEvent::Text`.
+    #[cfg(target_os = "android")]
+    Key {
+        key: Keycode,
+        pressed: bool,
+        repeat: bool,
+        modifiers: Modifiers,
+    },
     PtrMoved(Vec2),
     PtrPressed(PtrButton, Vec2),
     PtrReleased(PtrButton, Vec2),
     PtrLeft,
+    /// A pinch: (`current_span / previous_span`, current midpoint of the two pointers).
     Zoom(f32, Vec2),
+    /// A two-finger pan: the movement of the midpoint since the last update.
     Scroll(Vec2),
+    Gesture(Gesture),
+    /// A gamepad being connected, disconnected or having a button pressed (only sent if `TouchTranslater.gamepads` is set).
+    Gamepad(GamepadEvent),
+    /// A raw touch of a single finger or stylus, sent alongside the mouse emulation.
+    /// `size` is the (major, minor) axis of the touched area.
+    Touch {
+        id: u32,
+        phase: TouchPhase,
+        pos: Vec2,
+        pressure: f32,
+        size: Vec2,
+    },
+    /// A stylus touching the screen. Sent instead of `TouchEvent::Touch`, alongside the mouse emulation.
+    /// `tilt` is the angle away from perpendicular to the screen and `orientation` the direction it
+    /// points to (clockwise from up), both in radians.
+    Pen {
+        id: u32,
+        phase: TouchPhase,
+        pos: Vec2,
+        pressure: f32,
+        tilt: f32,
+        orientation: f32,
+        eraser: bool,
+    },
+}
+
+/// A source of timestamps for `TouchTranslater`.
+/// Timestamps must be monotonic and in the same time base as `MotionEvent::event_time()`.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> Duration;
+}
+
+/// The `CLOCK_MONOTONIC` clock, which `java.lang.System.nanoTime()` and `MotionEvent::event_time()` use.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MonotonicClock;
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `ts` is a valid timespec to write into.
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+        Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -82,7 +365,8 @@ pub struct TouchTranslater {
     prev_pointers: Vec<Ptr>,
     scale_factor: f32,
     ignore_release: bool,
-    last_press_time: SystemTime,
+    clock: Arc<dyn Clock>,
+    last_press_time: Duration,
     last_pos: Vec2,
     press_pos: Option<Vec2>,
     holding: bool,
@@ -90,6 +374,16 @@ pub struct TouchTranslater {
     pointers: Vec<Option<Ptr>>,
     zoom: Option<Zoom>,
     pub wants_zoom: bool,
+    pub wants_touches: bool,
+    pub gestures: Option<GestureRecognizer>,
+    pub gamepads: Option<Gamepads>,
+    // The dead key accent waiting to be combined with the next typed character.
+    #[cfg(target_os = "android")]
+    combining_accent: Option<char>,
+    pub text_input: TextInputSync,
+    // The mouse buttons held down, indexed by `PtrButton`.
+    #[cfg(target_os = "android")]
+    mouse_buttons: [bool; 5],
 }
 impl Default for TouchTranslater {
     fn default() -> Self {
@@ -97,7 +391,8 @@ impl Default for TouchTranslater {
             prev_pointers: Vec::new(),
             scale_factor: 1.0,
             ignore_release: false,
-            last_press_time: SystemTime::UNIX_EPOCH,
+            clock: Arc::new(MonotonicClock),
+            last_press_time: Duration::ZERO,
             last_pos: Vec2::ZERO,
             press_pos: None,
             holding: false,
@@ -105,21 +400,57 @@ impl Default for TouchTranslater {
             pointers: vec![],
             zoom: None,
             wants_zoom: true,
+            wants_touches: true,
+            gestures: None,
+            gamepads: None,
+            #[cfg(target_os = "android")]
+            combining_accent: None,
+            text_input: TextInputSync::default(),
+            #[cfg(target_os = "android")]
+            mouse_buttons: [false; 5],
         }
     }
 }
 impl TouchTranslater {
+    const LONG_PRESS_TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// Replaces the clock used by `update` and `update_pointers` (a `MonotonicClock` by default).
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Arc::new(clock);
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
     pub fn set_scale_factor(&mut self, f: f32) {
         self.scale_factor = f;
+        if let Some(gestures) = &mut self.gestures {
+            gestures.set_scale_factor(f);
+        }
     }
 
-    pub fn update_pointers(&mut self, ptrs: Vec<Ptr>, mut out: impl FnMut(TouchEvent)) {
+    pub fn update_pointers(&mut self, ptrs: Vec<Ptr>, out: impl FnMut(TouchEvent)) {
+        let now = self.clock.now();
+        self.update_pointers_at(ptrs, now, out);
+    }
+
+    /// Same as `update_pointers`, but with the time of the pointer update given by the caller.
+    pub fn update_pointers_at(
+        &mut self,
+        ptrs: Vec<Ptr>,
+        time: Duration,
+        mut out: impl FnMut(TouchEvent),
+    ) {
+        if let Some(gestures) = &mut self.gestures {
+            gestures.update_pointers(&ptrs, time, |g| out(TouchEvent::Gesture(g)));
+        }
         let mut temp_prev_pointers = Vec::new();
         std::mem::swap(&mut temp_prev_pointers, &mut self.prev_pointers);
 
         for change in pointers_diff(&temp_prev_pointers, &ptrs) {
             match change {
-                PtrChange::New(ptr) => self.phase_start(ptr.id as usize, ptr.pos, &mut out),
+                PtrChange::New(ptr) => self.phase_start(ptr.id as usize, ptr.pos, time, &mut out),
                 PtrChange::Rm(ptr) => self.phase_end(ptr.id as usize, ptr.pos, &mut out),
                 PtrChange::Move(ptr) => self.phase_move(ptr.id as usize, ptr.pos, &mut out),
             }
@@ -128,14 +459,29 @@ impl TouchTranslater {
         self.prev_pointers = ptrs;
     }
 
-    pub fn update(&mut self, mut out: impl FnMut(TouchEvent)) {
-        if self.holding
-            && SystemTime::now()
-                .duration_since(self.last_press_time)
-                .unwrap_or(Duration::ZERO)
-                .as_millis()
-                > 500
-        {
+    /// Whether `update` may still report a long press, so it must keep being called even without new input.
+    pub fn long_press_pending(&self) -> bool {
+        self.holding
+            || self
+                .gestures
+                .as_ref()
+                .is_some_and(|gestures| gestures.long_press_pending())
+    }
+
+    pub fn update(&mut self, out: impl FnMut(TouchEvent)) {
+        let now = self.clock.now();
+        self.update_at(now, out);
+    }
+
+    /// Same as `update`, but with the current time given by the caller.
+    pub fn update_at(&mut self, now: Duration, mut out: impl FnMut(TouchEvent)) {
+        if let Some(gestures) = &mut self.gestures {
+            gestures.update(now, |g| out(TouchEvent::Gesture(g)));
+        }
+        if let Some(gamepads) = &mut self.gamepads {
+            gamepads.update(|e| out(TouchEvent::Gamepad(e)));
+        }
+        if self.holding && now.saturating_sub(self.last_press_time) > Self::LONG_PRESS_TIMEOUT {
             out(TouchEvent::PtrPressed(
                 PtrButton::Secondary,
                 self.last_pos / self.scale_factor,
@@ -149,7 +495,18 @@ impl TouchTranslater {
         }
     }
 
-    pub fn phase_start(&mut self, idx: usize, pos: Vec2, mut out: impl FnMut(TouchEvent)) {
+    fn first_two_pointers(&self) -> Option<(Vec2, Vec2)> {
+        let mut pointers = self.pointers.iter().flatten();
+        Some((pointers.next()?.pos, pointers.next()?.pos))
+    }
+
+    pub fn phase_start(
+        &mut self,
+        idx: usize,
+        pos: Vec2,
+        time: Duration,
+        mut out: impl FnMut(TouchEvent),
+    ) {
         self.pointer_count += 1;
         if idx >= self.pointers.len() {
             self.pointers.resize(idx + 1, None);
@@ -166,16 +523,7 @@ impl TouchTranslater {
 
             out(TouchEvent::PtrLeft);
 
-            let mut pointers = self.pointers.iter().cloned().flatten();
-            let [a, b] = [pointers.next().unwrap(), pointers.next().unwrap()];
-            let dist = a.pos.distance_squared(b.pos);
-            let (min, max) = (a.pos.min(b.pos), a.pos.max(b.pos));
-            let anchor = min + (max - min) * 0.5;
-            self.zoom = Some(Zoom {
-                start_dist: dist,
-                prev_dist: dist,
-                anchor,
-            });
+            self.zoom = self.first_two_pointers().map(|(a, b)| Zoom::new(a, b));
         } else {
             out(TouchEvent::PtrMoved(pos / self.scale_factor));
             out(TouchEvent::PtrPressed(
@@ -184,7 +532,7 @@ impl TouchTranslater {
             ));
 
             self.last_pos = pos;
-            self.last_press_time = SystemTime::now();
+            self.last_press_time = time;
             self.press_pos = Some(pos);
             self.holding = true;
             self.ignore_release = false;
@@ -199,7 +547,7 @@ impl TouchTranslater {
 
         if let Some(press_pos) = self.press_pos {
             let press_dist = press_pos.distance_squared(pos).abs();
-            if press_dist >= 50.0 / scale_factor() * 50.0 / scale_factor() {
+            if press_dist >= 50.0 / self.scale_factor * 50.0 / self.scale_factor {
                 self.holding = false;
                 self.press_pos = None;
             }
@@ -207,15 +555,15 @@ impl TouchTranslater {
         if let Some(Some(ptr)) = self.pointers.get_mut(idx) {
             ptr.pos = pos;
         }
-        if let Some(zoom) = &mut self.zoom {
-            let mut pointers = self.pointers.iter().cloned().flatten();
-            let [a, b] = [pointers.next().unwrap(), pointers.next().unwrap()];
-            let dist = a.pos.distance_squared(b.pos);
-            if dist != zoom.start_dist {
-                let delta = ((dist - zoom.prev_dist) * 0.0001) + 1.0;
-                out(TouchEvent::Zoom(delta, zoom.anchor / self.scale_factor));
+        let pointers = self.first_two_pointers();
+        if let (Some(zoom), Some((a, b))) = (&mut self.zoom, pointers) {
+            let (ratio, delta) = zoom.update(a, b);
+            if ratio != 1.0 {
+                out(TouchEvent::Zoom(ratio, zoom.anchor / self.scale_factor));
+            }
+            if delta != Vec2::ZERO {
+                out(TouchEvent::Scroll(delta / self.scale_factor));
             }
-            zoom.prev_dist = dist;
         }
     }
 
@@ -245,28 +593,385 @@ impl TouchTranslater {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Edge {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// A high-level gesture recognized by a `GestureRecognizer`.
+/// Positions are in points, velocities in points per second, angles in radians.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// Sent on release of every tap, including the first tap of a double tap.
+    Tap(Vec2),
+    DoubleTap(Vec2),
+    LongPress(Vec2),
+    DragStart(Vec2),
+    DragEnd(Vec2),
+    /// Sent right before `DragEnd` if the pointer was released fast enough.
+    Fling {
+        pos: Vec2,
+        velocity: Vec2,
+    },
+    /// The change in angle (clockwise) between two pointers since the last update.
+    Rotate {
+        angle: f32,
+        anchor: Vec2,
+    },
+    /// A drag that started at an edge of the screen and moved inwards.
+    EdgeSwipe(Edge),
+}
+
+/// Thresholds used by a `GestureRecognizer`.
+/// Distances are in dp, velocities in dp per second.
+#[derive(Clone, Debug)]
+pub struct GestureConfig {
+    /// How far a pointer can move before a press becomes a drag.
+    pub touch_slop: f32,
+    /// How far apart two taps can be to count as a double tap.
+    pub double_tap_slop: f32,
+    pub double_tap_timeout: Duration,
+    pub long_press_timeout: Duration,
+    pub min_fling_velocity: f32,
+    /// The width of the area along the screen edges where edge swipes can start.
+    pub edge_size: f32,
+    /// How far a pointer has to move inwards for an edge swipe.
+    pub edge_swipe_distance: f32,
+}
+impl Default for GestureConfig {
+    fn default() -> Self {
+        // Matches the defaults of android.view.ViewConfiguration
+        Self {
+            touch_slop: 8.0,
+            double_tap_slop: 100.0,
+            double_tap_timeout: Duration::from_millis(300),
+            long_press_timeout: Duration::from_millis(500),
+            min_fling_velocity: 50.0,
+            edge_size: 20.0,
+            edge_swipe_distance: 40.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum GestureState {
+    Idle,
+    Pressed,
+    LongPressed,
+    Dragging,
+    MultiTouch,
+}
+
+/// Recognizes `Gesture`s from pointer updates.
+///
+/// Positions passed in are in pixels, `time` can have any origin, as long as it's monotonic.
+#[derive(Clone, Debug)]
+pub struct GestureRecognizer {
+    pub config: GestureConfig,
+    scale_factor: f32,
+    screen_size: Vec2,
+    prev_pointers: Vec<Ptr>,
+    state: GestureState,
+    down_pos: Vec2,
+    down_time: Duration,
+    edge: Option<Edge>,
+    last_tap: Option<(Vec2, Duration)>,
+    // (time, pos) of the recent moves of the primary pointer, used for fling velocity.
+    samples: Vec<(Duration, Vec2)>,
+    // (id, id, angle) of the two pointers of a rotation.
+    rotation: Option<(u32, u32, f32)>,
+}
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new(GestureConfig::default())
+    }
+}
+impl GestureRecognizer {
+    const VELOCITY_WINDOW: Duration = Duration::from_millis(100);
+
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            scale_factor: 1.0,
+            screen_size: Vec2::ZERO,
+            prev_pointers: Vec::new(),
+            state: GestureState::Idle,
+            down_pos: Vec2::ZERO,
+            down_time: Duration::ZERO,
+            edge: None,
+            last_tap: None,
+            samples: Vec::new(),
+            rotation: None,
+        }
+    }
+
+    pub fn set_scale_factor(&mut self, f: f32) {
+        self.scale_factor = f;
+    }
+
+    /// The size of the screen in pixels, edge swipes are not recognized while this is zero.
+    pub fn set_screen_size(&mut self, size: Vec2) {
+        self.screen_size = size;
+    }
+
+    fn dp(&self, v: f32) -> f32 {
+        v * self.scale_factor
+    }
+
+    pub fn update_pointers(&mut self, ptrs: &[Ptr], time: Duration, mut out: impl FnMut(Gesture)) {
+        let prev_pointers = std::mem::take(&mut self.prev_pointers);
+        for change in pointers_diff(&prev_pointers, ptrs) {
+            match change {
+                PtrChange::New(ptr) => self.pointer_down(ptr, ptrs, time),
+                PtrChange::Move(ptr) => self.pointer_move(ptr, ptrs, time, &mut out),
+                PtrChange::Rm(ptr) => self.pointer_up(ptr, ptrs, time, &mut out),
+            }
+        }
+        self.prev_pointers = ptrs.to_vec();
+    }
+
+    /// Whether a pointer is held down without moving, so `update` may still report a long press.
+    pub fn long_press_pending(&self) -> bool {
+        self.state == GestureState::Pressed
+    }
+
+    /// Checks for timed gestures (long-press). Should be called every frame.
+    pub fn update(&mut self, time: Duration, mut out: impl FnMut(Gesture)) {
+        if self.state == GestureState::Pressed
+            && time.saturating_sub(self.down_time) >= self.config.long_press_timeout
+        {
+            self.state = GestureState::LongPressed;
+            out(Gesture::LongPress(self.down_pos / self.scale_factor));
+        }
+    }
+
+    fn pointer_down(&mut self, ptr: Ptr, ptrs: &[Ptr], time: Duration) {
+        if ptrs.len() > 1 {
+            self.state = GestureState::MultiTouch;
+            self.edge = None;
+            if let [a, b, ..] = ptrs {
+                self.rotation = Some((a.id, b.id, (b.pos - a.pos).to_angle()));
+            }
+            return;
+        }
+        self.state = GestureState::Pressed;
+        self.down_pos = ptr.pos;
+        self.down_time = time;
+        self.samples.clear();
+        self.samples.push((time, ptr.pos));
+        self.edge = self.edge_at(ptr.pos);
+    }
+
+    fn pointer_move(
+        &mut self,
+        ptr: Ptr,
+        ptrs: &[Ptr],
+        time: Duration,
+        mut out: impl FnMut(Gesture),
+    ) {
+        if let Some((id_a, id_b, prev_angle)) = &mut self.rotation {
+            let a = ptrs.iter().find(|p| p.id == *id_a);
+            let b = ptrs.iter().find(|p| p.id == *id_b);
+            if let (Some(a), Some(b)) = (a, b) {
+                let angle = (b.pos - a.pos).to_angle();
+                let delta = wrap_angle(angle - *prev_angle);
+                *prev_angle = angle;
+                if delta != 0.0 {
+                    let anchor = (a.pos + b.pos) * 0.5 / self.scale_factor;
+                    out(Gesture::Rotate {
+                        angle: delta,
+                        anchor,
+                    });
+                }
+            }
+            return;
+        }
+        if ptrs.len() != 1 {
+            return;
+        }
+
+        self.samples.push((time, ptr.pos));
+        self.samples
+            .retain(|(t, _)| time.saturating_sub(*t) <= Self::VELOCITY_WINDOW);
+
+        if self.state == GestureState::Pressed
+            && ptr.pos.distance(self.down_pos) > self.dp(self.config.touch_slop)
+        {
+            self.state = GestureState::Dragging;
+            out(Gesture::DragStart(self.down_pos / self.scale_factor));
+        }
+        if let Some(edge) = self.edge {
+            let moved = ptr.pos - self.down_pos;
+            let inwards = match edge {
+                Edge::Top => moved.y,
+                Edge::Right => -moved.x,
+                Edge::Bottom => -moved.y,
+                Edge::Left => moved.x,
+            };
+            if inwards >= self.dp(self.config.edge_swipe_distance) {
+                self.edge = None;
+                out(Gesture::EdgeSwipe(edge));
+            }
+        }
+    }
+
+    fn pointer_up(&mut self, ptr: Ptr, ptrs: &[Ptr], time: Duration, mut out: impl FnMut(Gesture)) {
+        if let Some((id_a, id_b, _)) = self.rotation {
+            if ptr.id == id_a || ptr.id == id_b {
+                self.rotation = None;
+            }
+        }
+        if !ptrs.is_empty() {
+            return;
+        }
+        let pos = ptr.pos / self.scale_factor;
+        match self.state {
+            GestureState::Pressed => match self.last_tap {
+                Some((last_pos, last_time))
+                    if time.saturating_sub(last_time) <= self.config.double_tap_timeout
+                        && last_pos.distance(ptr.pos) <= self.dp(self.config.double_tap_slop) =>
+                {
+                    self.last_tap = None;
+                    out(Gesture::DoubleTap(pos));
+                }
+                _ => {
+                    self.last_tap = Some((ptr.pos, time));
+                    out(Gesture::Tap(pos));
+                }
+            },
+            GestureState::Dragging => {
+                let velocity = self.velocity() / self.scale_factor;
+                if velocity.length() >= self.config.min_fling_velocity {
+                    out(Gesture::Fling { pos, velocity });
+                }
+                out(Gesture::DragEnd(pos));
+            }
+            _ => {}
+        }
+        self.state = GestureState::Idle;
+        self.edge = None;
+    }
+
+    /// The velocity of the primary pointer over the last `VELOCITY_WINDOW`, in pixels per second.
+    fn velocity(&self) -> Vec2 {
+        let (Some(first), Some(last)) = (self.samples.first(), self.samples.last()) else {
+            return Vec2::ZERO;
+        };
+        let dt = last.0.saturating_sub(first.0).as_secs_f32();
+        if dt <= 0.0 {
+            return Vec2::ZERO;
+        }
+        (last.1 - first.1) / dt
+    }
+
+    fn edge_at(&self, pos: Vec2) -> Option<Edge> {
+        if self.screen_size == Vec2::ZERO {
+            return None;
+        }
+        let edge_size = self.dp(self.config.edge_size);
+        if pos.y <= edge_size {
+            Some(Edge::Top)
+        } else if pos.x >= self.screen_size.x - edge_size {
+            Some(Edge::Right)
+        } else if pos.y >= self.screen_size.y - edge_size {
+            Some(Edge::Bottom)
+        } else if pos.x <= edge_size {
+            Some(Edge::Left)
+        } else {
+            None
+        }
+    }
+}
+
+/// Wraps `angle` into the range `-PI..=PI`.
+fn wrap_angle(angle: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    let angle = angle.rem_euclid(TAU);
+    if angle > PI {
+        angle - TAU
+    } else {
+        angle
+    }
+}
+
+#[cfg(target_os = "android")]
 pub fn translate_input_event(
     event: &InputEvent,
     translater: &mut TouchTranslater,
     mut out: impl FnMut(TouchEvent),
 ) -> InputStatus {
+    if let Some(gamepads) = &mut translater.gamepads {
+        let status = gamepads.handle_input_event(event, |e| out(TouchEvent::Gamepad(e)));
+        if status == InputStatus::Handled {
+            return status;
+        }
+    }
     match event {
         InputEvent::KeyEvent(key_event) => {
-            let mut new_event = None;
+            let key = key_event.key_code();
+            if is_system_key(key) {
+                return InputStatus::Unhandled;
+            }
+            let pressed = match key_event.action() {
+                KeyAction::Down => true,
+                KeyAction::Up => false,
+                _ => return InputStatus::Unhandled,
+            };
+            let modifiers = Modifiers::from(key_event.meta_state());
+            out(TouchEvent::Key {
+                key,
+                pressed,
+                repeat: pressed && key_event.repeat_count() > 0,
+                modifiers,
+            });
+
+            // Shortcuts (eg: Ctrl+C) shouldn't type any text
+            if modifiers.ctrl || modifiers.meta {
+                return InputStatus::Handled;
+            }
             let combined_key_char =
-                character_map_and_combine_key(key_event, &mut None, &mut new_event);
+                character_map_and_combine_key(key_event, &mut translater.combining_accent);
             match combined_key_char {
-                Some(KeyMapChar::Unicode(ch)) | Some(KeyMapChar::CombiningAccent(ch)) => {
+                // Control characters (eg: '\n', '\t') are handled as `TouchEvent::Key`
+                Some(KeyMapChar::Unicode(ch)) if !ch.is_control() => {
                     out(TouchEvent::Text(ch.to_string()));
                 }
-                None => {}
+                Some(KeyMapChar::Unicode(_)) | Some(KeyMapChar::CombiningAccent(_)) | None => {}
                 other => log::warn!("unrecognized key_char: {other:?}"),
             }
-            if let Some(event) = new_event {
-                out(event);
-            }
+        }
+        InputEvent::MotionEvent(motion_event) if is_mouse(motion_event) => {
+            translate_mouse(motion_event, translater, out);
         }
         InputEvent::MotionEvent(motion_event) => {
+            match motion_event.action() {
+                // A hovering stylus
+                MotionAction::HoverEnter | MotionAction::HoverMove => {
+                    if translater.pointer_count == 0 {
+                        let ptr = motion_event.pointer_at_index(motion_event.pointer_index());
+                        out(TouchEvent::PtrMoved(
+                            vec2(ptr.x(), ptr.y()) / translater.scale_factor,
+                        ));
+                    }
+                    return InputStatus::Handled;
+                }
+                MotionAction::HoverExit => {
+                    if translater.pointer_count == 0 {
+                        out(TouchEvent::PtrLeft);
+                    }
+                    return InputStatus::Handled;
+                }
+                MotionAction::Scroll | MotionAction::ButtonPress | MotionAction::ButtonRelease => {
+                    return InputStatus::Handled;
+                }
+                _ => {}
+            }
+            if translater.wants_touches {
+                translate_touches(motion_event, translater.scale_factor, &mut out);
+            }
             let pointers: Vec<_> = motion_event.pointers().collect();
             let mut pointers: Vec<_> = pointers
                 .into_iter()
@@ -288,34 +993,168 @@ pub fn translate_input_event(
                     .0;
                 _ = pointers.remove(idx);
             }
-            translater.update_pointers(pointers, out);
+            let time = Duration::from_nanos(motion_event.event_time() as u64);
+            translater.update_pointers_at(pointers, time, out);
         }
         InputEvent::TextEvent(text_state) => {
-            log::info!("Android set text input to {text_state:?}")
+            translater.text_input.on_text_input_state(text_state);
         }
         _ => return InputStatus::Unhandled,
     }
     InputStatus::Handled
 }
 
+#[cfg(target_os = "android")]
+/// Whether `motion_event` comes from a mouse or a trackpad moving a mouse cursor.
+fn is_mouse(motion_event: &MotionEvent) -> bool {
+    let source: u32 = motion_event.source().into();
+    let mouse: u32 = Source::Mouse.into();
+    source & mouse == mouse
+        || motion_event
+            .pointers()
+            .any(|ptr| ptr.tool_type() == ToolType::Mouse)
+}
+
+#[cfg(target_os = "android")]
+/// How far one notch of a mouse wheel scrolls, in dp.
+/// Matches Android's default `ViewConfiguration.getScaledVerticalScrollFactor()`.
+const WHEEL_SCROLL_DP: f32 = 64.0;
+
+#[cfg(target_os = "android")]
+/// Translates mouse and trackpad input, which moves a cursor instead of emulating one with touches.
+fn translate_mouse(
+    motion_event: &MotionEvent,
+    translater: &mut TouchTranslater,
+    mut out: impl FnMut(TouchEvent),
+) {
+    let ptr = motion_event.pointer_at_index(motion_event.pointer_index());
+    let pos = vec2(ptr.x(), ptr.y()) / translater.scale_factor;
+    let action = motion_event.action();
+    match action {
+        MotionAction::HoverExit => {
+            out(TouchEvent::PtrLeft);
+            return;
+        }
+        MotionAction::Scroll => {
+            // Positive VSCROLL scrolls up and positive HSCROLL scrolls right
+            let ticks = vec2(
+                -ptr.axis_value(Axis::Hscroll),
+                ptr.axis_value(Axis::Vscroll),
+            );
+            if ticks != Vec2::ZERO {
+                out(TouchEvent::Scroll(ticks * WHEEL_SCROLL_DP));
+            }
+            return;
+        }
+        _ => out(TouchEvent::PtrMoved(pos)),
+    }
+
+    let state = motion_event.button_state();
+    let mut buttons = [
+        state.primary(),
+        state.secondary(),
+        state.teriary(),
+        state.back(),
+        state.forward(),
+    ];
+    match action {
+        // Trackpad taps don't always set a button
+        MotionAction::Down if !buttons.contains(&true) => {
+            buttons[PtrButton::Primary as usize] = true
+        }
+        MotionAction::Up | MotionAction::Cancel => buttons = [false; 5],
+        _ => {}
+    }
+    let all_buttons = [
+        PtrButton::Primary,
+        PtrButton::Secondary,
+        PtrButton::Middle,
+        PtrButton::Extra1,
+        PtrButton::Extra2,
+    ];
+    for button in all_buttons {
+        let idx = button as usize;
+        match (translater.mouse_buttons[idx], buttons[idx]) {
+            (false, true) => out(TouchEvent::PtrPressed(button, pos)),
+            (true, false) => out(TouchEvent::PtrReleased(button, pos)),
+            _ => {}
+        }
+    }
+    translater.mouse_buttons = buttons;
+}
+
+#[cfg(target_os = "android")]
+/// Emits a `TouchEvent::Touch` for every finger, or a `TouchEvent::Pen` for every stylus, in `motion_event`.
+fn translate_touches(
+    motion_event: &MotionEvent,
+    scale_factor: f32,
+    mut out: impl FnMut(TouchEvent),
+) {
+    let action = motion_event.action();
+    let action_idx = motion_event.pointer_index();
+    for ptr in motion_event.pointers() {
+        if matches!(ptr.tool_type(), ToolType::Mouse | ToolType::Palm) {
+            continue;
+        }
+        let is_action_ptr = ptr.pointer_index() == action_idx;
+        let phase = match action {
+            MotionAction::Down | MotionAction::PointerDown if is_action_ptr => TouchPhase::Start,
+            MotionAction::Up | MotionAction::PointerUp if is_action_ptr => TouchPhase::End,
+            MotionAction::Cancel => TouchPhase::Cancel,
+            MotionAction::Down
+            | MotionAction::PointerDown
+            | MotionAction::Up
+            | MotionAction::PointerUp
+            | MotionAction::Move => TouchPhase::Move,
+            _ => continue,
+        };
+        let id = ptr.pointer_id() as u32;
+        let pos = vec2(ptr.x(), ptr.y()) / scale_factor;
+        let tool_type = ptr.tool_type();
+        if matches!(tool_type, ToolType::Stylus | ToolType::Eraser) {
+            out(TouchEvent::Pen {
+                id,
+                phase,
+                pos,
+                pressure: ptr.pressure(),
+                tilt: ptr.axis_value(Axis::Tilt),
+                orientation: ptr.orientation(),
+                eraser: tool_type == ToolType::Eraser,
+            });
+        } else {
+            out(TouchEvent::Touch {
+                id,
+                phase,
+                pos,
+                pressure: ptr.pressure(),
+                size: vec2(ptr.touch_major(), ptr.touch_minor()) / scale_factor,
+            });
+        }
+    }
+}
+
+#[cfg(target_os = "android")]
+/// Keys that should be left for Android to handle (eg: changing the volume).
+fn is_system_key(key: Keycode) -> bool {
+    matches!(
+        key,
+        Keycode::VolumeUp
+            | Keycode::VolumeDown
+            | Keycode::VolumeMute
+            | Keycode::Power
+            | Keycode::Home
+            | Keycode::AppSwitch
+    )
+}
+
+#[cfg(target_os = "android")]
 /// Tries to map the `key_event` to a `KeyMapChar` containing a unicode character or dead key accent
 fn character_map_and_combine_key(
     key_event: &KeyEvent,
     combining_accent: &mut Option<char>,
-    app_event: &mut Option<TouchEvent>,
 ) -> Option<KeyMapChar> {
     let device_id = key_event.device_id();
 
-    use android_activity::input::Keycode;
-    if key_event.key_code() == Keycode::Del {
-        match key_event.action() {
-            KeyAction::Up => *app_event = Some(TouchEvent::KeyBackspace { pressed: false }),
-            KeyAction::Down => *app_event = Some(TouchEvent::KeyBackspace { pressed: true }),
-            _ => {}
-        }
-        return None;
-    }
-
     let key_map = match crate::android().device_key_character_map(device_id) {
         Ok(key_map) => key_map,
         Err(err) => {
@@ -368,3 +1207,556 @@ fn character_map_and_combine_key(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::vec2;
+    use std::sync::Mutex;
+
+    /// A clock that only moves when the test advances it.
+    #[derive(Clone, Debug, Default)]
+    struct FakeClock(Arc<Mutex<Duration>>);
+    impl FakeClock {
+        fn advance(&self, millis: u64) {
+            *self.0.lock().unwrap() += Duration::from_millis(millis);
+        }
+    }
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn translater() -> (TouchTranslater, FakeClock) {
+        let clock = FakeClock::default();
+        let mut translater = TouchTranslater::default();
+        translater.set_clock(clock.clone());
+        (translater, clock)
+    }
+
+    fn ptr(id: u32, x: f32, y: f32) -> Ptr {
+        Ptr {
+            pos: vec2(x, y),
+            id,
+        }
+    }
+
+    fn update_pointers(translater: &mut TouchTranslater, ptrs: &[Ptr]) -> Vec<TouchEvent> {
+        let mut events = vec![];
+        translater.update_pointers(ptrs.to_vec(), |e| events.push(e));
+        events
+    }
+
+    fn update(translater: &mut TouchTranslater) -> Vec<TouchEvent> {
+        let mut events = vec![];
+        translater.update(|e| events.push(e));
+        events
+    }
+
+    #[test]
+    fn zoom_ratio_is_relative_to_the_previous_span() {
+        let mut zoom = Zoom::new(vec2(0.0, 0.0), vec2(100.0, 0.0));
+        assert_eq!(zoom.start_span, 100.0);
+        assert_eq!(zoom.anchor, vec2(50.0, 0.0));
+
+        // Spreading symmetrically zooms in without panning.
+        assert_eq!(
+            zoom.update(vec2(-50.0, 0.0), vec2(150.0, 0.0)),
+            (2.0, Vec2::ZERO)
+        );
+        // Not squared: halving the span gives exactly 0.5.
+        assert_eq!(
+            zoom.update(vec2(0.0, 0.0), vec2(100.0, 0.0)),
+            (0.5, Vec2::ZERO)
+        );
+        assert_eq!(zoom.start_span, 100.0);
+        assert_eq!(zoom.prev_span, 100.0);
+
+        // The span is the distance, not only the horizontal part of it.
+        let (ratio, _) = zoom.update(vec2(0.0, 0.0), vec2(60.0, 80.0));
+        assert_eq!(ratio, 1.0);
+    }
+
+    #[test]
+    fn zoom_pans_with_the_midpoint() {
+        let mut zoom = Zoom::new(vec2(0.0, 0.0), vec2(100.0, 0.0));
+        assert_eq!(
+            zoom.update(vec2(10.0, 20.0), vec2(110.0, 20.0)),
+            (1.0, vec2(10.0, 20.0))
+        );
+        assert_eq!(zoom.anchor, vec2(60.0, 20.0));
+        // Only one finger moving pans by half its movement.
+        assert_eq!(
+            zoom.update(vec2(10.0, 20.0), vec2(130.0, 20.0)),
+            (1.2, vec2(10.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn zoom_ignores_zero_spans() {
+        let mut zoom = Zoom::new(vec2(10.0, 10.0), vec2(10.0, 10.0));
+        assert_eq!(
+            zoom.update(vec2(0.0, 10.0), vec2(20.0, 10.0)),
+            (1.0, Vec2::ZERO)
+        );
+        assert_eq!(
+            zoom.update(vec2(10.0, 10.0), vec2(10.0, 10.0)),
+            (1.0, Vec2::ZERO)
+        );
+        assert_eq!(
+            zoom.update(vec2(0.0, 10.0), vec2(20.0, 10.0)),
+            (1.0, Vec2::ZERO)
+        );
+    }
+
+    #[test]
+    fn press_and_release() {
+        let (mut translater, _clock) = translater();
+        translater.set_scale_factor(2.0);
+        assert_eq!(
+            update_pointers(&mut translater, &[ptr(0, 20.0, 40.0)]),
+            [
+                TouchEvent::PtrMoved(vec2(10.0, 20.0)),
+                TouchEvent::PtrPressed(PtrButton::Primary, vec2(10.0, 20.0)),
+            ]
+        );
+        assert!(translater.long_press_pending());
+        assert_eq!(
+            update_pointers(&mut translater, &[]),
+            [
+                TouchEvent::PtrReleased(PtrButton::Primary, vec2(10.0, 20.0)),
+                TouchEvent::PtrLeft,
+            ]
+        );
+        assert!(!translater.long_press_pending());
+        assert_eq!(update(&mut translater), []);
+    }
+
+    #[test]
+    fn moving_cancels_long_press() {
+        let (mut translater, clock) = translater();
+        update_pointers(&mut translater, &[ptr(0, 100.0, 100.0)]);
+
+        // Small moves keep the press
+        clock.advance(100);
+        assert_eq!(
+            update_pointers(&mut translater, &[ptr(0, 130.0, 100.0)]),
+            [TouchEvent::PtrMoved(vec2(130.0, 100.0))]
+        );
+        assert!(translater.long_press_pending());
+
+        // Moving 50 points away from the press cancels it
+        clock.advance(100);
+        update_pointers(&mut translater, &[ptr(0, 150.0, 100.0)]);
+        assert!(!translater.long_press_pending());
+        clock.advance(1000);
+        assert_eq!(update(&mut translater), []);
+    }
+
+    #[test]
+    fn long_press() {
+        let (mut translater, clock) = translater();
+        update_pointers(&mut translater, &[ptr(0, 100.0, 100.0)]);
+        clock.advance(400);
+        assert_eq!(update(&mut translater), []);
+        clock.advance(100);
+        assert_eq!(
+            update(&mut translater),
+            [],
+            "fires after, not at, the timeout"
+        );
+        clock.advance(1);
+        assert_eq!(
+            update(&mut translater),
+            [
+                TouchEvent::PtrPressed(PtrButton::Secondary, vec2(100.0, 100.0)),
+                TouchEvent::PtrReleased(PtrButton::Secondary, vec2(100.0, 100.0)),
+            ]
+        );
+        assert!(!translater.long_press_pending());
+        clock.advance(1000);
+        assert_eq!(update(&mut translater), [], "only fires once");
+    }
+
+    #[test]
+    fn long_press_uses_event_time() {
+        let (mut translater, clock) = translater();
+        clock.advance(10_000);
+        // The press happened 400ms before it's handled
+        let ptrs = vec![ptr(0, 100.0, 100.0)];
+        translater.update_pointers_at(ptrs, Duration::from_millis(9_600), |_| {});
+        assert_eq!(update(&mut translater), []);
+        clock.advance(101);
+        assert_eq!(update(&mut translater).len(), 2);
+
+        // `update_at` ignores the clock
+        let ptrs = vec![ptr(0, 100.0, 100.0)];
+        translater.update_pointers_at(vec![], Duration::from_millis(20_000), |_| {});
+        translater.update_pointers_at(ptrs, Duration::from_millis(20_000), |_| {});
+        let mut events = vec![];
+        translater.update_at(Duration::from_millis(20_400), |e| events.push(e));
+        assert_eq!(events, []);
+        translater.update_at(Duration::from_millis(20_501), |e| events.push(e));
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn zoom_start_and_end() {
+        let (mut translater, _clock) = translater();
+        update_pointers(&mut translater, &[ptr(0, 100.0, 100.0)]);
+
+        // The second finger ends the press, without releasing it
+        let mut ptrs = vec![ptr(0, 100.0, 100.0), ptr(1, 200.0, 100.0)];
+        assert_eq!(
+            update_pointers(&mut translater, &ptrs),
+            [TouchEvent::PtrLeft]
+        );
+        assert!(!translater.long_press_pending());
+
+        // Spreading the fingers to twice the distance, around the same midpoint
+        ptrs = vec![ptr(0, 50.0, 100.0), ptr(1, 250.0, 100.0)];
+        let events = update_pointers(&mut translater, &ptrs);
+        let zooms: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                TouchEvent::Zoom(ratio, anchor) => Some((*ratio, *anchor)),
+                _ => None,
+            })
+            .collect();
+        // One zoom per moved pointer: 150/100, then 200/150
+        assert_eq!(
+            zooms,
+            [
+                (1.5, vec2(125.0, 100.0)),
+                (200.0 / 150.0, vec2(150.0, 100.0))
+            ]
+        );
+        let total: f32 = zooms.iter().map(|(ratio, _)| ratio).product();
+        assert!((total - 2.0).abs() < 1e-6);
+
+        // Lifting a finger ends the zoom
+        ptrs = vec![ptr(0, 50.0, 100.0)];
+        assert_eq!(
+            update_pointers(&mut translater, &ptrs),
+            [TouchEvent::PtrReleased(
+                PtrButton::Primary,
+                vec2(250.0, 100.0)
+            )]
+        );
+        assert_eq!(
+            update_pointers(&mut translater, &[ptr(0, 10.0, 100.0)]),
+            [TouchEvent::PtrMoved(vec2(10.0, 100.0))]
+        );
+        assert_eq!(
+            update_pointers(&mut translater, &[]),
+            [
+                TouchEvent::PtrReleased(PtrButton::Primary, vec2(10.0, 100.0)),
+                TouchEvent::PtrLeft,
+            ]
+        );
+    }
+
+    #[test]
+    fn pointer_up_after_moves() {
+        let (mut translater, _clock) = translater();
+        update_pointers(&mut translater, &[ptr(0, 100.0, 100.0)]);
+        update_pointers(
+            &mut translater,
+            &[ptr(0, 100.0, 100.0), ptr(1, 200.0, 100.0)],
+        );
+
+        // Pointer 1 is lifted while pointer 0 moves: the zoom is updated before the release
+        assert_eq!(
+            update_pointers(&mut translater, &[ptr(0, 110.0, 100.0)]),
+            [
+                TouchEvent::Zoom(0.9, vec2(155.0, 100.0)),
+                TouchEvent::Scroll(vec2(5.0, 0.0)),
+                TouchEvent::PtrReleased(PtrButton::Primary, vec2(200.0, 100.0)),
+            ]
+        );
+        // A new pointer is pressed before the others move
+        assert_eq!(
+            update_pointers(
+                &mut translater,
+                &[ptr(0, 120.0, 100.0), ptr(2, 300.0, 100.0)]
+            ),
+            [
+                TouchEvent::PtrLeft,
+                TouchEvent::Zoom(180.0 / 190.0, vec2(210.0, 100.0)),
+                TouchEvent::Scroll(vec2(5.0, 0.0))
+            ]
+        );
+    }
+
+    fn gestures(recognizer: &mut GestureRecognizer, ptrs: &[Ptr], millis: u64) -> Vec<Gesture> {
+        let mut gestures = vec![];
+        recognizer.update_pointers(ptrs, Duration::from_millis(millis), |g| gestures.push(g));
+        gestures
+    }
+
+    fn tap(recognizer: &mut GestureRecognizer, x: f32, y: f32, millis: u64) -> Vec<Gesture> {
+        gestures(recognizer, &[ptr(0, x, y)], millis);
+        gestures(recognizer, &[], millis + 50)
+    }
+
+    fn assert_rotate(gestures: &[Gesture], expected_angle: f32, expected_anchor: Vec2) {
+        let [Gesture::Rotate { angle, anchor }] = gestures else {
+            panic!("expected a single rotation, got {gestures:?}");
+        };
+        assert!((angle - expected_angle).abs() < 1e-4, "angle {angle}");
+        assert!(anchor.abs_diff_eq(expected_anchor, 1e-4), "anchor {anchor}");
+    }
+
+    #[test]
+    fn tap_in_points() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.set_scale_factor(2.0);
+        assert_eq!(gestures(&mut recognizer, &[ptr(0, 100.0, 60.0)], 0), []);
+        // Moving within the touch slop (8dp = 16px) is still a tap
+        assert_eq!(gestures(&mut recognizer, &[ptr(0, 116.0, 60.0)], 20), []);
+        assert_eq!(
+            gestures(&mut recognizer, &[], 40),
+            [Gesture::Tap(vec2(58.0, 30.0))]
+        );
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        assert_eq!(
+            tap(&mut recognizer, 100.0, 100.0, 0),
+            [Gesture::Tap(vec2(100.0, 100.0))]
+        );
+        // Released 300ms after the first release, 100px away
+        assert_eq!(
+            tap(&mut recognizer, 200.0, 100.0, 300),
+            [Gesture::DoubleTap(vec2(200.0, 100.0))]
+        );
+        // A double tap doesn't start the next one
+        assert_eq!(
+            tap(&mut recognizer, 200.0, 100.0, 400),
+            [Gesture::Tap(vec2(200.0, 100.0))]
+        );
+    }
+
+    #[test]
+    fn double_tap_thresholds() {
+        let mut recognizer = GestureRecognizer::default();
+        tap(&mut recognizer, 100.0, 100.0, 0);
+        // Too late
+        assert_eq!(
+            tap(&mut recognizer, 100.0, 100.0, 301),
+            [Gesture::Tap(vec2(100.0, 100.0))]
+        );
+        // Too far
+        assert_eq!(
+            tap(&mut recognizer, 201.0, 100.0, 400),
+            [Gesture::Tap(vec2(201.0, 100.0))]
+        );
+        // Scaled by the density
+        recognizer.set_scale_factor(2.0);
+        assert_eq!(
+            tap(&mut recognizer, 401.0, 100.0, 500),
+            [Gesture::DoubleTap(vec2(200.5, 50.0))]
+        );
+    }
+
+    #[test]
+    fn gesture_long_press() {
+        let mut recognizer = GestureRecognizer::default();
+        let update = |recognizer: &mut GestureRecognizer, millis| {
+            let mut gestures = vec![];
+            recognizer.update(Duration::from_millis(millis), |g| gestures.push(g));
+            gestures
+        };
+        gestures(&mut recognizer, &[ptr(0, 50.0, 50.0)], 1000);
+        assert!(recognizer.long_press_pending());
+        assert_eq!(update(&mut recognizer, 1499), []);
+        assert_eq!(
+            update(&mut recognizer, 1500),
+            [Gesture::LongPress(vec2(50.0, 50.0))]
+        );
+        assert!(!recognizer.long_press_pending());
+        assert_eq!(update(&mut recognizer, 2000), []);
+        // Neither a tap nor a drag after a long press
+        assert_eq!(gestures(&mut recognizer, &[ptr(0, 150.0, 50.0)], 2100), []);
+        assert_eq!(gestures(&mut recognizer, &[], 2200), []);
+
+        // Dragging before the timeout cancels it
+        gestures(&mut recognizer, &[ptr(0, 50.0, 50.0)], 3000);
+        gestures(&mut recognizer, &[ptr(0, 59.0, 50.0)], 3100);
+        assert_eq!(update(&mut recognizer, 3500), []);
+    }
+
+    #[test]
+    fn drag_and_fling() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.set_scale_factor(2.0);
+        gestures(&mut recognizer, &[ptr(0, 100.0, 100.0)], 0);
+        // Just outside of the touch slop (16px)
+        assert_eq!(
+            gestures(&mut recognizer, &[ptr(0, 100.0, 117.0)], 10),
+            [Gesture::DragStart(vec2(50.0, 50.0))]
+        );
+        assert_eq!(gestures(&mut recognizer, &[ptr(0, 100.0, 137.0)], 20), []);
+        // 37px in 20ms is 1850px/s, 925dp/s
+        assert_eq!(
+            gestures(&mut recognizer, &[], 20),
+            [
+                Gesture::Fling {
+                    pos: vec2(50.0, 68.5),
+                    velocity: vec2(0.0, 925.0),
+                },
+                Gesture::DragEnd(vec2(50.0, 68.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn slow_drag_does_not_fling() {
+        let mut recognizer = GestureRecognizer::default();
+        gestures(&mut recognizer, &[ptr(0, 100.0, 100.0)], 0);
+        assert_eq!(
+            gestures(&mut recognizer, &[ptr(0, 120.0, 100.0)], 100),
+            [Gesture::DragStart(vec2(100.0, 100.0))]
+        );
+        gestures(&mut recognizer, &[ptr(0, 121.0, 100.0)], 200);
+        gestures(&mut recognizer, &[ptr(0, 122.0, 100.0)], 300);
+        // Only the last 100ms count: 10px/s, under the 50dp/s minimum
+        assert_eq!(
+            gestures(&mut recognizer, &[], 300),
+            [Gesture::DragEnd(vec2(122.0, 100.0))]
+        );
+    }
+
+    #[test]
+    fn rotation() {
+        use std::f32::consts::{FRAC_PI_4, PI};
+
+        let mut recognizer = GestureRecognizer::default();
+        gestures(&mut recognizer, &[ptr(0, 0.0, 0.0)], 0);
+        assert_eq!(
+            gestures(&mut recognizer, &[ptr(0, 0.0, 0.0), ptr(1, 100.0, 0.0)], 10),
+            []
+        );
+        // Clockwise on screen, since y points down
+        let rotate = gestures(
+            &mut recognizer,
+            &[ptr(0, 0.0, 0.0), ptr(1, 100.0, 100.0)],
+            20,
+        );
+        assert_rotate(&rotate, FRAC_PI_4, vec2(50.0, 50.0));
+        // Spreading without turning doesn't rotate
+        assert_eq!(
+            gestures(
+                &mut recognizer,
+                &[ptr(0, 0.0, 0.0), ptr(1, 200.0, 200.0)],
+                30
+            ),
+            []
+        );
+        // Crossing the -PI/PI boundary is a small turn, not a full one
+        gestures(
+            &mut recognizer,
+            &[ptr(0, 0.0, 0.0), ptr(1, -100.0, 1.0)],
+            40,
+        );
+        let rotate = gestures(
+            &mut recognizer,
+            &[ptr(0, 0.0, 0.0), ptr(1, -100.0, -1.0)],
+            50,
+        );
+        let expected = 2.0 * (PI - (1.0f32).atan2(-100.0));
+        assert_rotate(&rotate, expected, vec2(-50.0, -0.5));
+
+        // Lifting one of the pointers ends the rotation, and the release isn't a tap
+        assert_eq!(gestures(&mut recognizer, &[ptr(0, 0.0, 0.0)], 60), []);
+        assert_eq!(gestures(&mut recognizer, &[ptr(0, 50.0, 0.0)], 70), []);
+        assert_eq!(gestures(&mut recognizer, &[], 80), []);
+    }
+
+    /// Sends Android's state to `sync`, and applies the edit to `field` like a text field would.
+    fn type_text(
+        sync: &mut TextInputSync,
+        field: &mut String,
+        text: &str,
+        selection: Range<usize>,
+        compose: Option<Range<usize>>,
+    ) -> Option<TextFieldEdit> {
+        sync.pending = Some((text.to_owned(), selection, compose));
+        let edit = sync.take_edit()?;
+        let inserted = edit.text.clone() + edit.compose.as_deref().unwrap_or("");
+        let chars: Vec<char> = field.chars().collect();
+        *field = chars[..edit.replace.start].iter().collect::<String>()
+            + &inserted
+            + &chars[edit.replace.end..].iter().collect::<String>();
+        assert_eq!(field, text);
+        Some(edit)
+    }
+
+    #[test]
+    fn text_edit_composes_and_commits_words() {
+        let (mut sync, mut field) = (TextInputSync::default(), String::new());
+        let edit = type_text(&mut sync, &mut field, "h", 1..1, Some(0..1)).unwrap();
+        assert_eq!(edit.replace, 0..0);
+        assert_eq!(edit.compose.as_deref(), Some("h"));
+
+        let edit = type_text(&mut sync, &mut field, "hé", 2..2, Some(0..2)).unwrap();
+        assert_eq!((edit.replace, edit.text.as_str()), (0..1, ""));
+        assert_eq!(edit.compose.as_deref(), Some("hé"));
+
+        let edit = type_text(&mut sync, &mut field, "hé ", 3..3, None).unwrap();
+        assert_eq!((edit.replace, edit.text.as_str()), (2..2, " "));
+        assert!(edit.ends_composition);
+        assert_eq!(edit.selection, None);
+
+        let edit = type_text(&mut sync, &mut field, "hé w", 4..4, Some(3..4)).unwrap();
+        assert_eq!((edit.replace, edit.text.as_str()), (3..3, ""));
+        assert_eq!(edit.compose.as_deref(), Some("w"));
+
+        // The text field selects the composed text while Android places the cursor after it
+        assert!(!sync.set_from_app("hé w", 3..4));
+        assert_eq!(
+            type_text(&mut sync, &mut field, "hé w", 4..4, Some(3..4)),
+            None
+        );
+    }
+
+    #[test]
+    fn text_edit_replaces_deletes_and_selects() {
+        let (mut sync, mut field) = (TextInputSync::default(), String::from("hello world"));
+        assert!(sync.set_from_app("hello world", 11..11));
+
+        let edit = type_text(&mut sync, &mut field, "hello", 5..5, None).unwrap();
+        assert_eq!((edit.replace, edit.text.as_str()), (5..11, ""));
+        assert_eq!(edit.compose, None);
+
+        let edit = type_text(&mut sync, &mut field, "help", 4..4, None).unwrap();
+        assert_eq!((edit.replace, edit.text.as_str()), (3..5, "p"));
+
+        let edit = type_text(&mut sync, &mut field, "help", 1..3, None).unwrap();
+        assert_eq!((edit.replace, edit.text.as_str()), (4..4, ""));
+        assert_eq!(edit.selection, Some(1..3));
+
+        // Moving the cursor into a word composes it again
+        let edit = type_text(&mut sync, &mut field, "help", 2..2, Some(0..4)).unwrap();
+        assert_eq!((edit.replace, edit.text.as_str()), (0..4, ""));
+        assert_eq!(edit.compose.as_deref(), Some("help"));
+        assert!(!edit.ends_composition);
+    }
+
+    #[test]
+    fn text_edit_after_the_composed_text_finishes_the_composition() {
+        let (mut sync, mut field) = (TextInputSync::default(), String::from("ab"));
+        assert!(sync.set_from_app("ab", 2..2));
+        type_text(&mut sync, &mut field, "xab", 1..1, Some(0..1)).unwrap();
+
+        // The composed text can't be followed by inserted text in the same frame
+        let edit = type_text(&mut sync, &mut field, "xyab!", 2..2, Some(0..2)).unwrap();
+        assert_eq!((edit.replace, edit.text.as_str()), (1..3, "yab!"));
+        assert_eq!(edit.compose, None);
+        assert!(edit.ends_composition);
+        assert_eq!(edit.selection, Some(2..2));
+    }
+}
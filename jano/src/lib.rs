@@ -1,8 +1,30 @@
-#[cfg(any(feature = "egui_27", feature = "egui_28"))]
+#[cfg(all(feature = "accesskit", any(feature = "egui_27", feature = "egui_28")))]
+pub mod accessibility;
+#[cfg(target_os = "android")]
+pub mod clipboard;
+#[cfg(all(target_os = "android", any(feature = "egui_27", feature = "egui_28")))]
 pub mod egui_app;
+pub mod gamepad;
 #[cfg(any(feature = "wgpu_19", feature = "wgpu_20"))]
 pub mod graphics;
 pub mod input;
+#[cfg(any(feature = "egui_27", feature = "egui_28"))]
+pub mod system_fonts;
+#[cfg(any(feature = "egui_27", feature = "egui_28"))]
+pub mod texture_cache;
+
+#[cfg(all(feature = "egui_27", feature = "egui_28"))]
+compile_error!(
+    "Features `egui-0.27` and `egui-0.28` (or `egui`) can't be enabled at the same time"
+);
+#[cfg(all(feature = "wgpu_19", feature = "wgpu_20"))]
+compile_error!(
+    "Features `wgpu-0.19` and `wgpu-0.20` (or `wgpu`, `egui`) can't be enabled at the same time"
+);
+#[cfg(all(feature = "canvas", not(any(feature = "wgpu_19", feature = "wgpu_20"))))]
+compile_error!(
+    "Feature `canvas` needs a version of wgpu, enable `wgpu` (or `wgpu-0.19`, `wgpu-0.20`) too"
+);
 
 #[cfg(feature = "egui_27")]
 pub use egui_27 as egui;
@@ -20,33 +42,87 @@ pub use wgpu_20 as wgpu;
 #[cfg(feature = "serde")]
 pub use serde;
 
+#[cfg(target_os = "android")]
 pub use android_activity;
 pub use glam;
 pub use jni;
 pub use log;
+#[cfg(target_os = "android")]
 pub use ndk;
+#[cfg(target_os = "android")]
 pub use ndk_sys;
 
 pub use input::*;
 
-use android_activity::{AndroidApp, MainEvent, PollEvent};
+#[cfg(target_os = "android")]
+use android_activity::{input::Axis, AndroidApp, MainEvent, PollEvent};
 
-use glam::{uvec2, vec2, UVec2, Vec2};
+use glam::{vec2, UVec2, Vec2};
 
-use std::str::FromStr;
 use std::sync::atomic::{AtomicI32, Ordering};
-use std::sync::Mutex;
-use std::time::{Duration, SystemTime};
-
+#[cfg(target_os = "android")]
+use std::{
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(target_os = "android")]
 pub type Window = ndk::native_window::NativeWindow;
 
+#[cfg(target_os = "android")]
 static mut ANDROID: Option<AndroidApp> = None;
+#[cfg(target_os = "android")]
 pub fn android() -> &'static AndroidApp {
     // SAFETY: ANDROID is only ever mutated at the beginning of android_main, after that, it is perfectly safe to access ANDROID.
     let err = "ANDROID not initialized ; try caling jano::init_android() first";
     unsafe { ANDROID.as_ref().expect(err) }
 }
 
+#[cfg(target_os = "android")]
+/// Wakes `android_main` up if it's sleeping until the next event, so it notices what a JNI callback stored.
+/// Does nothing before `init_android`.
+pub(crate) fn wake_main_loop() {
+    // SAFETY: see `android()`
+    if let Some(android) = unsafe { ANDROID.as_ref() } {
+        android.create_waker().wake();
+    }
+}
+
+#[cfg(target_os = "android")]
+/// Runs `f` with the current thread's `JNIEnv` and the `MainActivity` instance.
+/// `call_activity` covers the common case of calling a single method.
+pub(crate) fn with_activity<T>(
+    f: impl FnOnce(&mut jni::JNIEnv, &jni::objects::JObject) -> Result<T, String>,
+) -> Result<T, String> {
+    let activity = android().activity_as_ptr();
+    let activity = unsafe { jni::objects::JObject::from_raw(activity as jni::sys::jobject) };
+    let vm = unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }
+        .map_err(|err| format!("Failed to get the JavaVM : {err:?}"))?;
+    let mut env = vm
+        .get_env()
+        .map_err(|err| format!("Failed to get the JNIEnv : {err:?}"))?;
+    f(&mut env, &activity)
+}
+
+#[cfg(target_os = "android")]
+/// Calls `MainActivity.{name}()` with the JNI signature `sig`, then converts the returned value with `f`.
+pub(crate) fn call_activity<T>(
+    name: &str,
+    sig: &str,
+    args: &[jni::objects::JValue],
+    f: impl FnOnce(&mut jni::JNIEnv, jni::objects::JValueOwned) -> Result<T, String>,
+) -> Result<T, String> {
+    with_activity(
+        |env, activity| match env.call_method(activity, name, sig, args) {
+            Ok(value) => f(env, value),
+            Err(err) => Err(format!(
+                "JNI call to MainActivity.{name}() failed : {err:?}"
+            )),
+        },
+    )
+}
+
 /// A raw picture obtained from MainActivity.takePicture().
 /// Always stored as ARGB, 1 byte per channel.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -56,9 +132,11 @@ pub struct Picture {
     pub size: UVec2,
 }
 
+#[cfg(target_os = "android")]
 /// The raw picture recieved from JVM when user submits photo for MainActivity.takePhoto().
 static PICTURE_TAKEN: Mutex<Option<Picture>> = Mutex::new(None);
 
+#[cfg(target_os = "android")]
 #[allow(dead_code)]
 #[allow(non_snake_case)]
 #[allow(clippy::not_unsafe_ptr_arg_deref)] // This code is only called by the Android JVM, so `buf` should be valid.
@@ -89,10 +167,11 @@ pub extern "C" fn Java_nodomain_jano_MainActivity_onPictureTaken(
     let buf_vec: Vec<u8> = unsafe { std::mem::transmute(buf_vec) };
     *PICTURE_TAKEN.lock().unwrap() = Some(Picture {
         data: buf_vec,
-        size: uvec2(w as u32, h as u32),
+        size: glam::uvec2(w as u32, h as u32),
     });
 
     log::info!("Rust onPictureTaken recieved {len} bytes");
+    wake_main_loop();
 }
 
 static TOP_DISPLAY_INSET: AtomicI32 = AtomicI32::new(0);
@@ -100,6 +179,7 @@ static RIGHT_DISPLAY_INSET: AtomicI32 = AtomicI32::new(0);
 static BOTTOM_DISPLAY_INSET: AtomicI32 = AtomicI32::new(0);
 static LEFT_DISPLAY_INSET: AtomicI32 = AtomicI32::new(0);
 
+#[cfg(target_os = "android")]
 #[allow(dead_code)]
 #[allow(non_snake_case)]
 #[allow(clippy::not_unsafe_ptr_arg_deref)] // This code is only called by the Android JVM, so `cutouts` should be valid.
@@ -128,6 +208,7 @@ pub extern "C" fn Java_nodomain_jano_MainActivity_onDisplayInsets(
     BOTTOM_DISPLAY_INSET.store(array[2], Ordering::Relaxed);
     LEFT_DISPLAY_INSET.store(array[3], Ordering::Relaxed);
     log::info!("Setting DISPLAY_INSETS to {array:?}");
+    wake_main_loop();
 }
 
 pub fn display_cutout(size: Vec2) -> (Vec2, Vec2) /* (min, max) */ {
@@ -143,6 +224,7 @@ pub fn display_cutout(size: Vec2) -> (Vec2, Vec2) /* (min, max) */ {
     )
 }
 
+#[cfg(target_os = "android")]
 pub fn init_android(android: AndroidApp) {
     // Enforce that ANDROID is only mutated once.
     // This is because all accesses to ANDROID via crate::android()
@@ -157,6 +239,26 @@ pub fn init_android(android: AndroidApp) {
         log::warn!("Attempted to init static ANDROID a second time");
         return;
     }
+    // Only X and Y are reported unless other axes are enabled
+    for axis in [
+        Axis::Pressure,
+        Axis::TouchMajor,
+        Axis::TouchMinor,
+        Axis::Orientation,
+        Axis::Tilt,
+        Axis::Vscroll,
+        Axis::Hscroll,
+        Axis::Z,
+        Axis::Rz,
+        Axis::Ltrigger,
+        Axis::Rtrigger,
+        Axis::Brake,
+        Axis::Gas,
+        Axis::HatX,
+        Axis::HatY,
+    ] {
+        android.enable_motion_axis(axis);
+    }
     unsafe { ANDROID = Some(android) }
 }
 
@@ -165,16 +267,25 @@ pub struct FrameStats {
     pub fps: u32,
 }
 
+#[cfg(target_os = "android")]
 pub trait AppState {
     fn on_main_event(&mut self, event: MainEvent, draw_frames: &mut bool) -> bool;
     fn on_frame(&mut self, stats: FrameStats);
+    /// How long `android_main` can sleep before the next `on_frame`, unless an event wakes it up first.
+    /// `None` sleeps until the next event (input, lifecycle, JNI callback or `AndroidApp::create_waker`).
+    /// By default, `on_frame` is called every `frame_time` (from `target_fps`).
+    fn frame_timeout(&mut self, frame_time: Duration) -> Option<Duration> {
+        Some(frame_time)
+    }
     fn on_picture_taken(&mut self, _pic: Picture) {}
+    fn on_clipboard_changed(&mut self) {}
 }
 
+#[cfg(target_os = "android")]
 pub fn android_main<A: AppState>(temp_android: AndroidApp, mut app: A, target_fps: u32) {
     init_android(temp_android);
 
-    let timeout = Duration::from_millis(1000 / target_fps as u64);
+    let frame_time = Duration::from_millis(1000 / target_fps as u64);
     let mut draw_frames = false;
     let mut frame_count = 0;
     let mut last_fps_update = SystemTime::now();
@@ -182,7 +293,11 @@ pub fn android_main<A: AppState>(temp_android: AndroidApp, mut app: A, target_fp
 
     let mut quit = false;
     while !quit {
-        android().poll_events(Some(timeout), |event| match event {
+        let timeout = match draw_frames {
+            true => app.frame_timeout(frame_time),
+            false => Some(frame_time),
+        };
+        android().poll_events(timeout, |event| match event {
             PollEvent::Wake => {}
             PollEvent::Timeout => {}
             PollEvent::Main(event) => {
@@ -209,6 +324,10 @@ pub fn android_main<A: AppState>(temp_android: AndroidApp, mut app: A, target_fp
             app.on_frame(FrameStats { fps });
         }
 
+        if clipboard::take_changed() {
+            app.on_clipboard_changed();
+        }
+
         let mut picture = PICTURE_TAKEN.lock().unwrap();
         if picture.is_some() {
             let pic = picture.take().unwrap();
@@ -217,26 +336,16 @@ pub fn android_main<A: AppState>(temp_android: AndroidApp, mut app: A, target_fp
     }
 }
 
+#[cfg(target_os = "android")]
 pub fn local_utc_offset() -> std::io::Result<i32> {
-    use jni::objects::JObject;
-
-    let activity = android().activity_as_ptr();
-    let activity = unsafe { JObject::from_raw(activity as jni::sys::jobject) };
-    let vm =
-        unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }.unwrap();
-    let mut env = vm.get_env().unwrap();
-
-    match env.call_method(activity, "localUtcOffset", "()I", &[]) {
-        Err(_err) => panic!("JNI call to SocketWrapper.localUtcOffset failed"),
-        Ok(obj) => match obj {
-            jni::objects::JValueGen::Int(v) => Ok(v),
-            _ => {
-                panic!("Java function SocketWrapper.localUtcOffset returned non-int value")
-            }
-        },
-    }
+    let offset = call_activity("localUtcOffset", "()I", &[], |_, value| match value {
+        jni::objects::JValueGen::Int(v) => Ok(v),
+        _ => Err("Java function MainActivity.localUtcOffset() returned non-int value".into()),
+    });
+    offset.map_err(std::io::Error::other)
 }
 
+#[cfg(target_os = "android")]
 pub fn get_java_io_err(env: &mut jni::JNIEnv) -> Option<std::io::Error> {
     let activity = android().activity_as_ptr();
     let activity = unsafe { jni::objects::JObject::from_raw(activity as jni::sys::jobject) };
@@ -289,143 +398,136 @@ pub fn get_java_io_err(env: &mut jni::JNIEnv) -> Option<std::io::Error> {
     Some(std::io::Error::new(kind, msg))
 }
 
+#[cfg(target_os = "android")]
 pub fn take_picture() -> Result<(), String> {
-    use jni::objects::JObject;
+    call_activity("takePicture", "()V", &[], |_, _| Ok(()))
+}
 
-    let activity = android().activity_as_ptr();
-    let activity = unsafe { JObject::from_raw(activity as jni::sys::jobject) };
-    let vm =
-        unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }.unwrap();
-    let mut env = vm.get_env().unwrap();
-    match env.call_method(activity, "takePicture", "()V", &[]) {
-        Ok(_) => {}
-        Err(err) => Err(format!(
-            "JNI call to MainActivity.takePicture() failed : {err:?}"
-        ))?,
+#[cfg(target_os = "android")]
+pub fn hide_system_ui() -> Result<(), String> {
+    call_activity("hideSystemUI", "()V", &[], |_, _| Ok(()))
+}
+
+#[cfg(target_os = "android")]
+/// The density of the display as a scale factor from dp to pixels (eg: 2.75 for a 440dpi display).
+/// Read from the app's `AConfiguration`, falling back to `DisplayMetrics.density`.
+pub fn display_density() -> Result<f32, String> {
+    if let Some(dpi) = android().config().density() {
+        return Ok(dpi as f32 / 160.0);
     }
-    Ok(())
+    call_activity("getDisplayDensity", "()F", &[], |_, value| match value {
+        jni::objects::JValueGen::Float(density) => Ok(density),
+        _ => Err("Java function MainActivity.getDisplayDensity() returned non-float value".into()),
+    })
 }
 
-pub fn hide_system_ui() -> Result<(), String> {
-    use jni::objects::JObject;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
 
-    let activity = android().activity_as_ptr();
-    let activity = unsafe { JObject::from_raw(activity as jni::sys::jobject) };
-    let vm =
-        unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }.unwrap();
-    let mut env = vm.get_env().unwrap();
-    match env.call_method(activity, "hideSystemUI", "()V", &[]) {
-        Ok(_) => {}
-        Err(err) => Err(format!(
-            "JNI call to MainActivity.hideSystemUI() failed : {err:?}"
-        ))?,
+#[cfg(target_os = "android")]
+/// Whether the system is in dark mode (`Configuration.uiMode`), `None` if it isn't known.
+pub fn system_theme() -> Option<Theme> {
+    use ndk::configuration::UiModeNight;
+    match android().config().ui_mode_night() {
+        UiModeNight::Yes => Some(Theme::Dark),
+        UiModeNight::No => Some(Theme::Light),
+        _ => None,
     }
-    Ok(())
 }
 
-pub fn set_keyboard_visibility(vis: bool) -> Result<(), String> {
-    use jni::objects::JObject;
+#[cfg(target_os = "android")]
+/// The user's font size preference (`Configuration.fontScale`), as a factor of the default size.
+pub fn font_scale() -> Result<f32, String> {
+    call_activity("getFontScale", "()F", &[], |_, value| match value {
+        jni::objects::JValueGen::Float(scale) => Ok(scale),
+        _ => Err("Java function MainActivity.getFontScale() returned non-float value".into()),
+    })
+}
 
-    let activity = android().activity_as_ptr();
-    let activity = unsafe { JObject::from_raw(activity as jni::sys::jobject) };
-    let vm =
-        unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }.unwrap();
-    let mut env = vm.get_env().unwrap();
+#[cfg(target_os = "android")]
+pub fn set_keyboard_visibility(vis: bool) -> Result<(), String> {
     let method = match vis {
         true => "showSoftKeyboard",
         false => "hideSoftKeyboard",
     };
-    if let Err(_err) = env.call_method(activity, method, "()V", &[]) {
-        Err(format!("JNI call to MainActivity.{method}() failed"))?
-    }
-    Ok(())
+    call_activity(method, "()V", &[], |_, _| Ok(()))
 }
 
+#[cfg(target_os = "android")]
 pub fn show_toast(msg: impl Into<String>, long_dur: bool) -> Result<(), String> {
     let msg = msg.into();
-    use jni::objects::{JObject, JString};
+    with_activity(|env, activity| {
+        let Ok(msg_jstr) = env.new_string(msg) else {
+            Err(String::from("JNI jstring creation failed"))?
+        };
+        match env.call_method(
+            activity,
+            "showToast",
+            "(Ljava/lang/String;Z)V",
+            &[(&msg_jstr).into(), long_dur.into()],
+        ) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!(
+                "JNI call to MainActivity.showToast() failed : {err:?}"
+            )),
+        }
+    })
+}
 
-    let activity = android().activity_as_ptr();
-    let activity = unsafe { JObject::from_raw(activity as jni::sys::jobject) };
-    let vm =
-        unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }.unwrap();
-    let mut env = vm.get_env().unwrap();
-
-    let msg_jstr: JString = match env.new_string(msg) {
-        Ok(v) => v,
-        Err(_) => Err(String::from("JNI jstring creation failed"))?,
-    };
+#[cfg(target_os = "android")]
+/// The primary clip as plain text. See `clipboard` for HTML, URIs and images.
+pub fn get_clipboard_content() -> Result<String, String> {
+    clipboard::get_text()?.ok_or_else(|| String::from("The clipboard is empty"))
+}
 
-    if let Err(err) = env.call_method(
-        activity,
-        "showToast",
-        "(Ljava/lang/String;Z)V",
-        &[(&msg_jstr).into(), long_dur.into()],
-    ) {
-        Err(format!(
-            "JNI call to MainActivity.showToast() failed : {err:?}"
-        ))?
-    }
-    Ok(())
+#[cfg(target_os = "android")]
+pub fn set_clipboard_content(value: &str) -> Result<(), String> {
+    clipboard::set_text(value)
 }
 
-pub fn get_clipboard_content() -> Result<String, String> {
-    use jni::objects::{JObject, JString, JValueGen};
+#[cfg(target_os = "android")]
+/// Opens `url` in the app the user chose for it (eg: the browser), through an `ACTION_VIEW` intent.
+pub fn open_url(url: &str) -> Result<(), String> {
+    use jni::objects::JValueGen;
 
-    let activity = android().activity_as_ptr();
-    let activity = unsafe { JObject::from_raw(activity as jni::sys::jobject) };
-    let vm =
-        unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }.unwrap();
-    let mut env = vm.get_env().unwrap();
-
-    match env.call_method(activity, "getClipboardContent", "()Ljava/lang/String;", &[]) {
-        Ok(s) => {
-            let JValueGen::Object(object) = s else {
-                Err(String::from(
-                    "Java function MainActivity.getClipboardContent() returned non-Object value",
-                ))?
-            };
-            // check if the object returned was null
-            if object.as_raw() as usize == 0 {
-                Err(String::from(
-                    "Java function MainActivity.getClipboardContent() returned null",
-                ))?
-            }
-            Ok(env
-                .get_string(&JString::from(object))
-                .unwrap()
-                .to_string_lossy()
-                .to_string())
+    with_activity(|env, activity| {
+        let Ok(java_url) = env.new_string(url) else {
+            Err(String::from("JNI jstring creation failed"))?
+        };
+        match env.call_method(
+            activity,
+            "openUrl",
+            "(Ljava/lang/String;)Z",
+            &[(&java_url).into()],
+        ) {
+            Ok(JValueGen::Bool(0)) => Err(format!("No app found to open URL {url}")),
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!(
+                "JNI call to MainActivity.openUrl() failed : {err:?}"
+            )),
         }
-        Err(_) => Err("JNI call to MainActivity.getClipboardContent() failed".into()),
-    }
+    })
 }
 
-pub fn set_clipboard_content(value: &str) -> Result<(), String> {
-    use jni::objects::{JObject, JString};
-
-    let activity = android().activity_as_ptr();
-    let activity = unsafe { JObject::from_raw(activity as jni::sys::jobject) };
-    let vm =
-        unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }.unwrap();
-    let mut env = vm.get_env().unwrap();
-
-    let java_value: JString = match env.new_string(value) {
-        Ok(v) => v,
-        Err(_) => Err(String::from("JNI jstring creation failed"))?,
-    };
+#[cfg(target_os = "android")]
+/// Tells the soft keyboard where the text cursor is: (left, top, right, bottom) in pixels.
+/// Floating and handwriting keyboards use this to stay out of the way.
+pub fn set_ime_cursor_rect(rect: [i32; 4]) -> Result<(), String> {
+    let args = rect.map(jni::objects::JValue::Int);
+    call_activity("setImeCursorRect", "(IIII)V", &args, |_, _| Ok(()))
+}
 
-    match env.call_method(
-        activity,
-        "setClipboardContent",
-        "(Ljava/lang/String;)V",
-        &[(&java_value).into()],
-    ) {
-        Err(_) => Err("JNI call to MainActivity.setClipboardContent() failed".into()),
-        Ok(_) => Ok(()),
-    }
+#[cfg(target_os = "android")]
+/// Changes the mouse pointer icon to one of the `android.view.PointerIcon.TYPE_*` constants.
+pub fn set_pointer_icon(icon_type: i32) -> Result<(), String> {
+    let args = [jni::objects::JValue::Int(icon_type)];
+    call_activity("setPointerIcon", "(I)V", &args, |_, _| Ok(()))
 }
 
+#[cfg(target_os = "android")]
 /// A good-enough drop-in-replacement of std::net::TcpStream
 ///
 /// Implemented functions:
@@ -460,6 +562,7 @@ pub fn set_clipboard_content(value: &str) -> Result<(), String> {
 ///
 #[derive(Debug)]
 pub struct TcpStream(jni::objects::GlobalRef);
+#[cfg(target_os = "android")]
 impl TcpStream {
     pub fn as_raw(&self) -> &jni::objects::GlobalRef {
         &self.0
@@ -677,6 +780,7 @@ impl TcpStream {
         Ok(std::net::SocketAddr::new(addr, port as u16))
     }
 }
+#[cfg(target_os = "android")]
 impl std::io::Write for TcpStream {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         let vm = unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }
@@ -723,6 +827,7 @@ impl std::io::Write for TcpStream {
         }
     }
 }
+#[cfg(target_os = "android")]
 impl std::io::Read for TcpStream {
     // FIXME: every value in `buf` gets overridden even if the Socket doen't read buf.len() bytes.
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -779,6 +884,7 @@ impl std::io::Read for TcpStream {
         Ok(())
     }
 }
+#[cfg(target_os = "android")]
 impl std::ops::Drop for TcpStream {
     fn drop(&mut self) {
         let vm = unsafe { jni::JavaVM::from_raw(android().vm_as_ptr() as *mut jni::sys::JavaVM) }
@@ -0,0 +1,476 @@
+//! Loads the device's fonts into egui, for the scripts egui's bundled fonts don't cover (eg: CJK, Arabic).
+//!
+//! The fallback fonts are listed in the system's fonts config (`fonts.xml`),
+//! each one is only read from `/system/fonts` when text in one of its scripts is first seen.
+
+use super::egui;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+pub const FONTS_DIR: &str = "/system/fonts";
+/// Where Android lists its fonts: `font_fallback.xml` since Android 15, `fonts.xml` before.
+pub const CONFIG_FILES: [&str; 2] = ["/system/etc/font_fallback.xml", "/system/etc/fonts.xml"];
+
+/// The writing systems system fonts can be loaded for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Script {
+    Japanese,
+    Korean,
+    /// Chinese characters, also covered by the Japanese and Korean fonts.
+    Han,
+    Arabic,
+    Hebrew,
+    Thai,
+    Devanagari,
+    Bengali,
+    Tamil,
+}
+impl Script {
+    /// The order scripts are loaded in, so that the Japanese or Korean font is used for their Han characters.
+    const PRIORITY: [Self; 9] = [
+        Self::Japanese,
+        Self::Korean,
+        Self::Han,
+        Self::Arabic,
+        Self::Hebrew,
+        Self::Thai,
+        Self::Devanagari,
+        Self::Bengali,
+        Self::Tamil,
+    ];
+
+    /// The script of `c`, `None` if it's covered by egui's fonts.
+    pub fn of(c: char) -> Option<Self> {
+        Some(match c as u32 {
+            0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Self::Japanese,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Self::Korean,
+            0x3000..=0x303F | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF => Self::Han,
+            0x20000..=0x2FFFF => Self::Han,
+            0x0600..=0x06FF | 0x0750..=0x077F | 0x08A0..=0x08FF => Self::Arabic,
+            0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Self::Arabic,
+            0x0590..=0x05FF | 0xFB1D..=0xFB4F => Self::Hebrew,
+            0x0E00..=0x0E7F => Self::Thai,
+            0x0900..=0x097F => Self::Devanagari,
+            0x0980..=0x09FF => Self::Bengali,
+            0x0B80..=0x0BFF => Self::Tamil,
+            _ => return None,
+        })
+    }
+
+    /// The scripts covered by a font for the language tag `lang` (eg: `ja`, `und-Arab`, `zh-Hans`).
+    fn from_lang(lang: &str) -> &'static [Self] {
+        let mut subtags = lang.split('-');
+        let language = subtags.next().unwrap_or_default();
+        let script = subtags.find(|subtag| subtag.len() == 4);
+        match (language, script) {
+            ("ja", _) | (_, Some("Jpan" | "Hira" | "Kana")) => &[Self::Japanese, Self::Han],
+            ("ko", _) | (_, Some("Kore" | "Hang")) => &[Self::Korean, Self::Han],
+            ("zh", _) | (_, Some("Hans" | "Hant" | "Hani")) => &[Self::Han],
+            (_, Some("Arab")) => &[Self::Arabic],
+            (_, Some("Hebr")) => &[Self::Hebrew],
+            (_, Some("Thai")) => &[Self::Thai],
+            (_, Some("Deva")) => &[Self::Devanagari],
+            (_, Some("Beng")) => &[Self::Bengali],
+            (_, Some("Taml")) => &[Self::Tamil],
+            // Includes color emoji (und-Zsye), which egui can't draw
+            _ => &[],
+        }
+    }
+}
+
+/// A font from the fallback chain of the fonts config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FallbackFont {
+    /// The file name, in the fonts directory.
+    pub file: String,
+    /// The index of the font in a font collection (`.ttc`).
+    pub index: u32,
+    pub scripts: Vec<Script>,
+}
+
+enum Node<'a> {
+    Open {
+        name: &'a str,
+        attrs: Vec<(&'a str, &'a str)>,
+        self_closing: bool,
+    },
+    Close(&'a str),
+    Text(&'a str),
+}
+
+/// Splits `xml` into tags and text, skipping comments and declarations. Only handles what fonts configs use.
+fn xml_nodes(mut xml: &str) -> Vec<Node<'_>> {
+    let mut nodes = vec![];
+    while !xml.is_empty() {
+        let Some(start) = xml.find('<') else {
+            nodes.push(Node::Text(xml));
+            break;
+        };
+        if start > 0 {
+            nodes.push(Node::Text(&xml[..start]));
+        }
+        xml = &xml[start..];
+        let end = match xml.starts_with("<!--") {
+            true => xml.find("-->").map(|end| end + 3),
+            false => xml.find('>').map(|end| end + 1),
+        };
+        let Some(end) = end else {
+            break;
+        };
+        let tag = &xml[1..end - 1];
+        xml = &xml[end..];
+
+        if tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            nodes.push(Node::Close(name.trim()));
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, mut rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let mut attrs = vec![];
+        while let Some((key, value)) = rest.split_once('=') {
+            let value = value.trim_start();
+            let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                break;
+            };
+            let Some((value, after)) = value[1..].split_once(quote) else {
+                break;
+            };
+            attrs.push((key.trim(), value));
+            rest = after;
+        }
+        nodes.push(Node::Open {
+            name,
+            attrs,
+            self_closing,
+        });
+    }
+    nodes
+}
+
+/// The fallback fonts listed in a fonts config (`fonts.xml`), in order.
+/// Only the unnamed families are fallbacks, for each one the regular (400, normal) font is picked.
+pub fn parse_fallbacks(xml: &str) -> Vec<FallbackFont> {
+    struct Font {
+        file: String,
+        index: u32,
+        regular: bool,
+    }
+
+    let mut fallbacks = vec![];
+    // The languages of the fallback family being read, and its fonts.
+    let mut family: Option<(&str, Vec<Font>)> = None;
+    let mut font: Option<Font> = None;
+
+    for node in xml_nodes(xml) {
+        match node {
+            Node::Open {
+                name: "family",
+                attrs,
+                self_closing: false,
+            } => {
+                let attr = |key| attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+                // Named families (eg: sans-serif) and fallbacks for them (eg: serif variants) aren't fallbacks for all text
+                if attr("name").is_none() && attr("fallbackFor").is_none() {
+                    family = Some((attr("lang").unwrap_or_default(), vec![]));
+                }
+            }
+            Node::Open {
+                name: "font",
+                attrs,
+                self_closing: false,
+            } if family.is_some() => {
+                let attr = |key| attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+                // Serif variants
+                if attr("fallbackFor").is_some() {
+                    continue;
+                }
+                font = Some(Font {
+                    file: String::new(),
+                    index: attr("index").and_then(|i| i.parse().ok()).unwrap_or(0),
+                    regular: attr("weight").unwrap_or("400") == "400"
+                        && attr("style").unwrap_or("normal") == "normal",
+                });
+            }
+            Node::Text(text) => {
+                if let Some(font) = &mut font {
+                    if font.file.is_empty() {
+                        font.file = text.trim().to_string();
+                    }
+                }
+            }
+            Node::Close("font") => {
+                if let (Some((_, fonts)), Some(font)) = (&mut family, font.take()) {
+                    fonts.push(font);
+                }
+            }
+            Node::Close("family") => {
+                let Some((langs, fonts)) = family.take() else {
+                    continue;
+                };
+                let mut scripts: Vec<Script> = vec![];
+                for lang in langs.split_whitespace() {
+                    for script in Script::from_lang(lang) {
+                        if !scripts.contains(script) {
+                            scripts.push(*script);
+                        }
+                    }
+                }
+                let font = fonts
+                    .iter()
+                    .find(|font| font.regular)
+                    .or(fonts.first())
+                    .filter(|font| !font.file.is_empty());
+                if let (false, Some(font)) = (scripts.is_empty(), font) {
+                    fallbacks.push(FallbackFont {
+                        file: font.file.clone(),
+                        index: font.index,
+                        scripts,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    fallbacks
+}
+
+/// Adds system fonts to egui's font definitions as text in new scripts is seen.
+pub struct SystemFonts {
+    fonts_dir: PathBuf,
+    fallbacks: Vec<FallbackFont>,
+    // The scripts a font was loaded for (or failed to load for).
+    loaded: HashSet<Script>,
+    /// The app's font definitions, with the system fonts loaded so far added after the app's fonts.
+    pub definitions: egui::FontDefinitions,
+}
+impl SystemFonts {
+    /// Uses the fallback fonts from `config` (the contents of a `fonts.xml`), found in `fonts_dir`.
+    pub fn new(
+        fonts_dir: impl Into<PathBuf>,
+        config: &str,
+        definitions: egui::FontDefinitions,
+    ) -> Self {
+        Self {
+            fonts_dir: fonts_dir.into(),
+            fallbacks: parse_fallbacks(config),
+            loaded: HashSet::new(),
+            definitions,
+        }
+    }
+
+    /// Uses the device's fonts config and fonts.
+    pub fn from_system(definitions: egui::FontDefinitions) -> Result<Self, String> {
+        for path in CONFIG_FILES {
+            if let Ok(config) = std::fs::read_to_string(path) {
+                return Ok(Self::new(FONTS_DIR, &config, definitions));
+            }
+        }
+        Err(format!(
+            "Failed to read a fonts config from {CONFIG_FILES:?}"
+        ))
+    }
+
+    pub fn fallbacks(&self) -> &[FallbackFont] {
+        &self.fallbacks
+    }
+
+    pub fn fonts_dir(&self) -> &Path {
+        &self.fonts_dir
+    }
+
+    /// Loads the fonts for the scripts in `text` that weren't seen yet.
+    /// Returns true if `definitions` changed, and has to be passed to `egui::Context::set_fonts`.
+    pub fn add_fonts_for(&mut self, text: &str) -> bool {
+        let scripts: HashSet<Script> = text
+            .chars()
+            .filter_map(Script::of)
+            .filter(|script| !self.loaded.contains(script))
+            .collect();
+        let mut changed = false;
+        for script in Script::PRIORITY {
+            if scripts.contains(&script) {
+                changed |= self.add_script(script);
+            }
+        }
+        changed
+    }
+
+    /// Loads the first fallback font for `script`, unless one was loaded already.
+    /// Returns true if `definitions` changed.
+    pub fn add_script(&mut self, script: Script) -> bool {
+        if !self.loaded.insert(script) {
+            return false;
+        }
+        let Some(font) = self
+            .fallbacks
+            .iter()
+            .find(|font| font.scripts.contains(&script))
+        else {
+            log::warn!("No system font for {script:?}");
+            return false;
+        };
+        self.loaded.extend(font.scripts.iter().copied());
+
+        let name = format!("system:{}#{}", font.file, font.index);
+        if self.definitions.font_data.contains_key(&name) {
+            return false;
+        }
+        let path = self.fonts_dir.join(&font.file);
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                log::warn!("Failed to read font {path:?} for {script:?}: {err}");
+                return false;
+            }
+        };
+        log::info!("Loaded font {path:?} for {script:?}");
+        let mut data = egui::FontData::from_owned(data);
+        data.index = font.index;
+        self.definitions.font_data.insert(name.clone(), data);
+        for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+            self.definitions
+                .families
+                .entry(family)
+                .or_default()
+                .push(name.clone());
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> (PathBuf, String) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let config = std::fs::read_to_string(dir.join("fonts.xml")).unwrap();
+        (dir.join("fonts"), config)
+    }
+
+    fn system_fonts() -> SystemFonts {
+        let (fonts_dir, config) = fixture();
+        SystemFonts::new(fonts_dir, &config, egui::FontDefinitions::default())
+    }
+
+    /// The system fonts added to `fonts`, and the file each one was read from.
+    fn loaded(fonts: &SystemFonts) -> Vec<(String, String)> {
+        let families = &fonts.definitions.families[&egui::FontFamily::Proportional];
+        families
+            .iter()
+            .filter(|name| name.starts_with("system:"))
+            .map(|name| {
+                let data = &fonts.definitions.font_data[name];
+                (name.clone(), String::from_utf8(data.font.to_vec()).unwrap())
+            })
+            .collect()
+    }
+
+    fn font(file: &str, index: u32, scripts: &[Script]) -> FallbackFont {
+        FallbackFont {
+            file: file.to_string(),
+            index,
+            scripts: scripts.to_vec(),
+        }
+    }
+
+    #[test]
+    fn fallbacks_of_the_config() {
+        let (_, config) = fixture();
+        assert_eq!(
+            parse_fallbacks(&config),
+            [
+                // Only the 400 normal font of the family
+                font("NotoNaskhArabic-Regular.ttf", 0, &[Script::Arabic]),
+                // Without the family and font that are fallbacks for serif
+                font("NotoSansHebrew-Regular.ttf", 0, &[Script::Hebrew]),
+                font("NotoSansCJK-Regular.ttc", 2, &[Script::Han]),
+                font(
+                    "NotoSansCJK-Regular.ttc",
+                    0,
+                    &[Script::Japanese, Script::Han]
+                ),
+                font("NotoSansCJK-Regular.ttc", 1, &[Script::Korean, Script::Han]),
+                font("NotoSansThai-Regular.ttf", 0, &[Script::Thai]),
+                // No emoji, and neither sans-serif nor serif
+            ]
+        );
+    }
+
+    #[test]
+    fn fonts_are_loaded_once_when_first_seen() {
+        let mut fonts = system_fonts();
+        let default_fonts = fonts.definitions.font_data.len();
+        assert!(!fonts.add_fonts_for("Hello, world"));
+        assert_eq!(fonts.definitions.font_data.len(), default_fonts);
+
+        assert!(fonts.add_fonts_for("مرحبا"));
+        let arabic = (
+            "system:NotoNaskhArabic-Regular.ttf#0".to_string(),
+            "NotoNaskhArabic-Regular.ttf".to_string(),
+        );
+        assert_eq!(loaded(&fonts), std::slice::from_ref(&arabic));
+        let monospace = &fonts.definitions.families[&egui::FontFamily::Monospace];
+        assert_eq!(monospace.last(), Some(&arabic.0));
+
+        // Already loaded
+        assert!(!fonts.add_fonts_for("مرحبا بالعالم"));
+        assert!(!fonts.add_script(Script::Arabic));
+        assert_eq!(loaded(&fonts), [arabic]);
+        assert_eq!(fonts.definitions.font_data.len(), default_fonts + 1);
+    }
+
+    #[test]
+    fn collection_index_is_kept() {
+        let mut fonts = system_fonts();
+        assert!(fonts.add_fonts_for("한국어"));
+        let font = &fonts.definitions.font_data["system:NotoSansCJK-Regular.ttc#1"];
+        assert_eq!(font.index, 1);
+        assert_eq!(*font.font, *b"NotoSansCJK-Regular.ttc");
+    }
+
+    #[test]
+    fn japanese_is_preferred_over_han() {
+        // Kanji before kana, in the same text
+        let mut fonts = system_fonts();
+        assert!(fonts.add_fonts_for("漢字とかな"));
+        let names: Vec<_> = loaded(&fonts).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["system:NotoSansCJK-Regular.ttc#0"]);
+        // The Japanese font covers Han too
+        assert!(!fonts.add_fonts_for("汉字"));
+
+        // Korean is loaded after Japanese, Han isn't loaded again
+        assert!(fonts.add_fonts_for("漢字 한글"));
+        let names: Vec<_> = loaded(&fonts).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(
+            names,
+            [
+                "system:NotoSansCJK-Regular.ttc#0",
+                "system:NotoSansCJK-Regular.ttc#1"
+            ]
+        );
+    }
+
+    #[test]
+    fn han_alone_uses_the_first_han_font() {
+        let mut fonts = system_fonts();
+        assert!(fonts.add_fonts_for("汉字"));
+        let names: Vec<_> = loaded(&fonts).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["system:NotoSansCJK-Regular.ttc#2"]);
+    }
+
+    #[test]
+    fn missing_fonts_are_only_tried_once() {
+        let mut fonts = system_fonts();
+        assert!(!fonts.add_fonts_for("สวัสดี"));
+        assert!(loaded(&fonts).is_empty());
+        // No font for Devanagari in the config
+        assert!(!fonts.add_fonts_for("नमस्ते"));
+        assert!(fonts.loaded.contains(&Script::Thai));
+        assert!(fonts.loaded.contains(&Script::Devanagari));
+    }
+}
@@ -0,0 +1,349 @@
+//! Keeps the egui textures of an app by id, with an optional memory budget.
+
+use super::egui;
+use crate::Picture;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+struct Entry {
+    handle: egui::TextureHandle,
+    // Hash of the pixels the texture was created from, `None` if it's only updated explicitly.
+    hash: Option<u64>,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Textures loaded into an `egui::Context`, by id.
+///
+/// Textures are kept until `remove`d, unless a byte budget is set with `set_budget`:
+/// the least recently used ones are then freed once the budget is exceeded.
+/// A freed texture is loaded again the next time it's obtained.
+#[derive(Default)]
+pub struct TextureCache {
+    entries: HashMap<egui::Id, Entry>,
+    budget: Option<usize>,
+    bytes: usize,
+    // Incremented on every access, for LRU eviction.
+    tick: u64,
+    pub options: egui::TextureOptions,
+}
+impl TextureCache {
+    /// The memory used by the textures (4 bytes per pixel).
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, id: impl Into<egui::Id>) -> bool {
+        self.entries.contains_key(&id.into())
+    }
+
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Sets how many bytes the textures can use before the least recently used ones are freed, `None` for no limit.
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+        self.evict(None);
+    }
+
+    /// The texture for `id`, if it's loaded.
+    pub fn get(&mut self, id: impl Into<egui::Id>) -> Option<egui::TextureHandle> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(&id.into())?;
+        entry.last_used = self.tick;
+        Some(entry.handle.clone())
+    }
+
+    /// The texture for `id`, loaded from `image()` if it isn't loaded yet.
+    /// The texture isn't updated when the image changes, call `update` for that.
+    pub fn get_or_insert_with(
+        &mut self,
+        ctx: &egui::Context,
+        id: impl Into<egui::Id>,
+        image: impl FnOnce() -> egui::ColorImage,
+    ) -> egui::TextureHandle {
+        let id = id.into();
+        match self.get(id) {
+            Some(handle) => handle,
+            None => self.insert(ctx, id, image(), None),
+        }
+    }
+
+    /// The texture for `id`, loaded again if `pic` changed since it was last loaded.
+    /// Hashes the pixels of `pic` on every call, prefer `get_or_insert_with` and `update` for big pictures that rarely change.
+    pub fn load_pic(
+        &mut self,
+        ctx: &egui::Context,
+        id: impl Into<egui::Id>,
+        pic: &Picture,
+    ) -> egui::TextureHandle {
+        let hash = content_hash(&pic.data, [pic.size.x as usize, pic.size.y as usize]);
+        self.load_hashed(ctx, id.into(), hash, || {
+            let size = [pic.size.x as usize, pic.size.y as usize];
+            egui::ColorImage::from_rgba_unmultiplied(size, &pic.data)
+        })
+    }
+
+    /// Same as `load_pic`, for an egui image.
+    pub fn load_img(
+        &mut self,
+        ctx: &egui::Context,
+        id: impl Into<egui::Id>,
+        img: egui::ColorImage,
+    ) -> egui::TextureHandle {
+        let hash = content_hash(pixel_bytes(&img.pixels), img.size);
+        self.load_hashed(ctx, id.into(), hash, || img)
+    }
+
+    /// The texture for `id`, loaded again from `image()` when `version` changes.
+    /// `version` is a cheap key that changes with the image, such as an edit counter or a file's modification time.
+    pub fn load_versioned(
+        &mut self,
+        ctx: &egui::Context,
+        id: impl Into<egui::Id>,
+        version: impl Hash,
+        image: impl FnOnce() -> egui::ColorImage,
+    ) -> egui::TextureHandle {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        version.hash(&mut hasher);
+        self.load_hashed(ctx, id.into(), hasher.finish(), image)
+    }
+
+    fn load_hashed(
+        &mut self,
+        ctx: &egui::Context,
+        id: egui::Id,
+        hash: u64,
+        image: impl FnOnce() -> egui::ColorImage,
+    ) -> egui::TextureHandle {
+        match self.entries.get(&id) {
+            Some(entry) if entry.hash == Some(hash) => self.get(id).unwrap(),
+            Some(_) => self.replace(id, image(), Some(hash)),
+            None => self.insert(ctx, id, image(), Some(hash)),
+        }
+    }
+
+    /// Replaces the texture for `id` with `image` (keeping its `TextureId`), or loads it.
+    pub fn update(
+        &mut self,
+        ctx: &egui::Context,
+        id: impl Into<egui::Id>,
+        image: egui::ColorImage,
+    ) -> egui::TextureHandle {
+        let id = id.into();
+        match self.entries.contains_key(&id) {
+            true => self.replace(id, image, None),
+            false => self.insert(ctx, id, image, None),
+        }
+    }
+
+    /// Frees the texture for `id` (once the handles given out are dropped). Returns false if it wasn't loaded.
+    pub fn remove(&mut self, id: impl Into<egui::Id>) -> bool {
+        match self.entries.remove(&id.into()) {
+            Some(entry) => {
+                self.bytes -= entry.bytes;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Frees all the textures (eg: when the system is low on memory).
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    fn insert(
+        &mut self,
+        ctx: &egui::Context,
+        id: egui::Id,
+        image: egui::ColorImage,
+        hash: Option<u64>,
+    ) -> egui::TextureHandle {
+        self.tick += 1;
+        let bytes = image.pixels.len() * 4;
+        let debug_id = format!("jano::TextureCache {id:?}");
+        let handle = ctx.load_texture(debug_id, image, self.options);
+        self.entries.insert(
+            id,
+            Entry {
+                handle: handle.clone(),
+                hash,
+                bytes,
+                last_used: self.tick,
+            },
+        );
+        self.bytes += bytes;
+        self.evict(Some(id));
+        handle
+    }
+
+    fn replace(
+        &mut self,
+        id: egui::Id,
+        image: egui::ColorImage,
+        hash: Option<u64>,
+    ) -> egui::TextureHandle {
+        self.tick += 1;
+        let entry = self.entries.get_mut(&id).unwrap();
+        let bytes = image.pixels.len() * 4;
+        entry.handle.set(image, self.options);
+        entry.hash = hash;
+        entry.last_used = self.tick;
+        self.bytes = self.bytes - entry.bytes + bytes;
+        entry.bytes = bytes;
+        let handle = entry.handle.clone();
+        self.evict(Some(id));
+        handle
+    }
+
+    /// Frees the least recently used textures until the budget is met, except for `keep`.
+    fn evict(&mut self, keep: Option<egui::Id>) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        while self.bytes > budget {
+            let lru = self
+                .entries
+                .iter()
+                .filter(|(id, _)| Some(**id) != keep)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| *id);
+            let Some(lru) = lru else {
+                break;
+            };
+            self.remove(lru);
+        }
+    }
+}
+
+fn content_hash(data: &[u8], size: [usize; 2]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    size.hash(&mut hasher);
+    hasher.write(data);
+    hasher.finish()
+}
+
+fn pixel_bytes(pixels: &[egui::Color32]) -> &[u8] {
+    // SAFETY: Color32 is a `repr(C)` [u8; 4]
+    unsafe { std::slice::from_raw_parts(pixels.as_ptr() as *const u8, pixels.len() * 4) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::uvec2;
+
+    fn image(width: usize, color: egui::Color32) -> egui::ColorImage {
+        egui::ColorImage::new([width, 1], color)
+    }
+
+    fn update(
+        cache: &mut TextureCache,
+        ctx: &egui::Context,
+        id: &'static str,
+        width: usize,
+    ) -> egui::TextureId {
+        cache.update(ctx, id, image(width, egui::Color32::RED)).id()
+    }
+
+    fn pic(data: [u8; 4]) -> Picture {
+        Picture {
+            data: data.to_vec(),
+            size: uvec2(1, 1),
+        }
+    }
+
+    // The textures uploaded or changed since the last call.
+    fn uploaded(ctx: &egui::Context) -> Vec<egui::TextureId> {
+        let delta = ctx.tex_manager().write().take_delta();
+        delta.set.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn least_recently_used_textures_are_evicted_first() {
+        let ctx = egui::Context::default();
+        let mut cache = TextureCache::default();
+        cache.set_budget(Some(8));
+        update(&mut cache, &ctx, "a", 1);
+        update(&mut cache, &ctx, "b", 1);
+        assert!(cache.get("a").is_some());
+
+        update(&mut cache, &ctx, "c", 1);
+        assert!(cache.contains("a") && cache.contains("c"));
+        assert!(!cache.contains("b"));
+        assert_eq!(cache.bytes(), 8);
+
+        // A texture bigger than the budget is kept, on its own
+        update(&mut cache, &ctx, "d", 3);
+        assert_eq!((cache.len(), cache.bytes()), (1, 12));
+        assert!(cache.contains("d"));
+    }
+
+    #[test]
+    fn replacing_a_texture_updates_the_byte_count() {
+        let ctx = egui::Context::default();
+        let mut cache = TextureCache::default();
+        let a = update(&mut cache, &ctx, "a", 1);
+        update(&mut cache, &ctx, "b", 2);
+        assert_eq!(cache.bytes(), 12);
+
+        let replaced = update(&mut cache, &ctx, "a", 4);
+        assert_eq!(replaced, a);
+        assert_eq!((cache.len(), cache.bytes()), (2, 24));
+
+        // Shrinking the budget keeps the most recently used texture
+        cache.set_budget(Some(20));
+        assert!(cache.contains("a") && !cache.contains("b"));
+        assert_eq!(cache.bytes(), 16);
+
+        assert!(cache.remove("a"));
+        assert!(!cache.remove("a"));
+        assert_eq!((cache.len(), cache.bytes()), (0, 0));
+    }
+
+    #[test]
+    fn load_pic_reloads_when_the_pixels_change() {
+        let ctx = egui::Context::default();
+        let mut cache = TextureCache::default();
+        uploaded(&ctx);
+        let first = cache.load_pic(&ctx, "pic", &pic([255, 0, 0, 255]));
+        assert_eq!(uploaded(&ctx), vec![first.id()]);
+
+        let same = cache.load_pic(&ctx, "pic", &pic([255, 0, 0, 255]));
+        assert_eq!(same.id(), first.id());
+        assert_eq!(uploaded(&ctx), vec![]);
+
+        let second = cache.load_pic(&ctx, "pic", &pic([0, 255, 0, 255]));
+        assert_eq!(second.id(), first.id());
+        assert_eq!(uploaded(&ctx), vec![first.id()]);
+        assert_eq!(cache.bytes(), 4);
+    }
+
+    #[test]
+    fn load_versioned_reloads_when_the_version_changes() {
+        let ctx = egui::Context::default();
+        let mut cache = TextureCache::default();
+        let red = || image(1, egui::Color32::RED);
+        let handle = cache.load_versioned(&ctx, "img", 1, red);
+        uploaded(&ctx);
+
+        let same = cache.load_versioned(&ctx, "img", 1, || unreachable!());
+        assert_eq!(same.id(), handle.id());
+        assert_eq!(uploaded(&ctx), vec![]);
+
+        let reloaded = cache.load_versioned(&ctx, "img", 2, red);
+        assert_eq!(reloaded.id(), handle.id());
+        assert_eq!(uploaded(&ctx), vec![handle.id()]);
+    }
+}